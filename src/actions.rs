@@ -1,4 +1,6 @@
 use super::app;
+use super::config;
+use super::ui::panels::content::Node;
 
 mod instructions;
 use instructions::Instruction;
@@ -7,8 +9,21 @@ pub const RUN: &str = "r";
 pub const WRITE: &str = "w";
 pub const WRITE_OUT: &str = "wo";
 pub const WRITE_CMD: &str = "wc";
+pub const WRITE_SCRIPT: &str = "ws";
+pub const WRITE_SELECTION: &str = "wsel";
+pub const RUN_SELECTION: &str = "rsel";
+pub const YANK: &str = "y";
+pub const YANK_OUT: &str = "yo";
+pub const YANK_CMD: &str = "yc";
+pub const EDIT: &str = "e";
+pub const RELOAD: &str = "reload";
+pub const WATCH: &str = "watch";
+pub const PIPE_SHOW: &str = "!";
+pub const PIPE_REPLACE: &str = "|";
+pub const BOOKMARK_ADD: &str = "ba";
+pub const BOOKMARKS: &str = "bookmarks";
 
-pub fn run(command: &str, state: &app::State) -> Result<String, String> {
+pub fn run(command: &str, state: &mut app::State) -> Result<String, String> {
     let cmd: Vec<&str> = command.splitn(2, ' ').collect();
     let mut param = "";
     if cmd.len() > 1 {
@@ -20,45 +35,304 @@ pub fn run(command: &str, state: &app::State) -> Result<String, String> {
     if instruction.starts_with(':') {
         instruction = instruction.chars().skip(1).collect();
     }
+    if let Some(shell) = instruction.strip_prefix(PIPE_SHOW) {
+        let shell = format!("{} {}", shell, param);
+        return instructions::new(Instruction::PipeShow, shell, false).eval(state);
+    }
+    if let Some(shell) = instruction.strip_prefix(PIPE_REPLACE) {
+        let shell = format!("{} {}", shell, param);
+        return instructions::new(Instruction::PipeReplace, shell, false).eval(state);
+    }
+    let force = instruction.ends_with('!');
+    if force {
+        instruction.pop();
+    }
     let instruction = instruction;
+    if instruction.parse::<usize>().is_ok() {
+        return instructions::new(Instruction::Jump, instruction, force).eval(state);
+    }
     let inst = match instruction.as_str() {
-        RUN => instructions::new(Instruction::Jq, param.to_string()),
-        WRITE => instructions::new(Instruction::WriteOut, param.to_string()),
-        WRITE_OUT => instructions::new(Instruction::WriteOut, param.to_string()),
-        WRITE_CMD => instructions::new(Instruction::WriteCmd, param.to_string()),
+        RUN => instructions::new(Instruction::Jq, param.to_string(), force),
+        WRITE => instructions::new(Instruction::WriteOut, param.to_string(), force),
+        WRITE_OUT => instructions::new(Instruction::WriteOut, param.to_string(), force),
+        WRITE_CMD => instructions::new(Instruction::WriteCmd, param.to_string(), force),
+        EDIT => instructions::new(Instruction::Open, param.to_string(), force),
+        RELOAD => instructions::new(Instruction::Reload, param.to_string(), force),
+        WATCH => instructions::new(Instruction::Watch, param.to_string(), force),
+        WRITE_SCRIPT => instructions::new(Instruction::WriteScript, param.to_string(), force),
+        WRITE_SELECTION => instructions::new(Instruction::WriteSelection, param.to_string(), force),
+        RUN_SELECTION => instructions::new(Instruction::RunSelection, param.to_string(), force),
+        YANK => instructions::new(Instruction::YankOut, param.to_string(), force),
+        YANK_OUT => instructions::new(Instruction::YankOut, param.to_string(), force),
+        YANK_CMD => instructions::new(Instruction::YankCmd, param.to_string(), force),
+        BOOKMARK_ADD => instructions::new(Instruction::BookmarkAdd, param.to_string(), force),
+        BOOKMARKS => instructions::new(Instruction::Bookmarks, param.to_string(), force),
 
-        _ => instructions::new(Instruction::Unknown, command.to_string()),
+        _ => instructions::new(Instruction::Unknown, command.to_string(), force),
     };
     inst.eval(state)
 }
 
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+
+pub enum WriteMode {
+    Create,
+    Overwrite,
+    Append,
+}
+
+/// Resolves the file name: absolute, relative to home (with `~`) or to cwd.
+fn resolve_path(fname: &str) -> Result<PathBuf, String> {
+    let home = || env::var("HOME").map_err(|_| String::from("Unable to resolve home directory"));
+    if fname == "~" {
+        return Ok(PathBuf::from(home()?));
+    }
+    if let Some(rest) = fname.strip_prefix("~/") {
+        return Ok(PathBuf::from(home()?).join(rest));
+    }
+    let cwd = env::current_dir().map_err(|e| format!("Error resolving cwd: {}", e))?;
+    Ok(cwd.join(fname))
+}
+
+/// Paths starting with the partial one, directories with a trailing slash.
+/// Hidden files are only listed when asked for with a leading dot.
+pub fn complete_path(partial: &str) -> Vec<String> {
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(idx) => partial.split_at(idx + 1),
+        None => ("", partial),
+    };
+    let listing = match resolve_path(dir).map(fs::read_dir) {
+        Ok(Ok(listing)) => listing,
+        _ => return Vec::new(),
+    };
+    let mut candidates: Vec<String> = listing
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, slash))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// Writes the file atomically, by writing to a temporary file next to it
+/// and renaming it into place. An existing file keeps its permissions, and
+/// symlinks are written through.
+fn write_file(fname: &str, content: &str, mode: WriteMode) -> Result<String, String> {
+    let path = resolve_path(fname)?;
+    let name = path.display().to_string();
+    let exists = path.exists();
+    if path.is_dir() {
+        return Err(format!("{} is a directory", name));
+    }
+    if exists {
+        if let WriteMode::Create = mode {
+            return Err(format!("{} already exists (add ! to overwrite)", name));
+        }
+    }
+    let mut data = Vec::new();
+    if let (WriteMode::Append, true) = (mode, exists) {
+        data = fs::read(&path).map_err(|e| format!("Error reading {}: {}", name, e))?;
+    }
+    data.extend_from_slice(content.as_bytes());
+
+    let (path, permissions) = match exists {
+        true => {
+            let target =
+                fs::canonicalize(&path).map_err(|e| format!("Error resolving {}: {}", name, e))?;
+            let metadata =
+                fs::metadata(&target).map_err(|e| format!("Error reading {}: {}", name, e))?;
+            (target, Some(metadata.permissions()))
+        }
+        false => (path, None),
+    };
+    let mut tmp = path.clone();
+    tmp.set_file_name(format!(
+        ".{}.{}.tmp",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        process::id()
+    ));
+    let written = File::create(&tmp)
+        .and_then(|mut file| file.write_all(&data).and_then(|_| file.sync_all()))
+        .and_then(|_| match permissions {
+            Some(permissions) => fs::set_permissions(&tmp, permissions),
+            None => Ok(()),
+        })
+        .and_then(|_| fs::rename(&tmp, &path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("Error writing {}: {}", name, e));
+    }
+    Ok(name)
+}
+
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Runs the command to completion, with the input (if any) on its stdin,
+/// killing it if it takes longer than the timeout (in seconds, 0 to wait forever).
+pub fn exec(mut command: Command, input: Option<&str>, timeout: u64) -> Result<Output, String> {
+    let name = command.get_program().to_string_lossy().to_string();
+    let stdin = match input {
+        Some(_) => Stdio::piped(),
+        None => Stdio::null(),
+    };
+    let mut child = command
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Unable to run {}: {}", name, e))?;
+
+    let mut stdout = child.stdout.take().expect("Missing stdout pipe");
+    let mut stderr = child.stderr.take().expect("Missing stderr pipe");
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        let input = input.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()));
+    }
+    let out = thread::spawn(move || {
+        let mut buf = Vec::new();
+        stdout.read_to_end(&mut buf).map(|_| buf)
+    });
+    let err = thread::spawn(move || {
+        let mut buf = Vec::new();
+        stderr.read_to_end(&mut buf).map(|_| buf)
+    });
 
-fn write_file(fname: &str, content: &str) -> String {
-    let mut cwd = env::current_dir().expect("Error resolving cwd");
-    cwd.push(fname);
-    let path = cwd.to_str().expect("Error resolving cwd file path");
-    let mut file = File::create(path).expect("Error creating file");
-    file.write_all(content.as_bytes())
-        .expect("Error writing file!");
-    String::from(path)
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => (),
+            Err(e) => return Err(format!("Error waiting for {}: {}", name, e)),
+        }
+        if timeout > 0 && started.elapsed() > Duration::from_secs(timeout) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("{} timed out after {}s", name, timeout));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Output {
+        status,
+        stdout: out.join().ok().and_then(|r| r.ok()).unwrap_or_default(),
+        stderr: err.join().ok().and_then(|r| r.ok()).unwrap_or_default(),
+    })
+}
+
+/// Lists every node in the JSON file as a path, key, type and value row.
+const LIST_NODES: &str = r#"
+def segment: if type == "number" then "[\(.)]"
+    elif test("^[A-Za-z_][A-Za-z0-9_]*$") then ".\(.)"
+    else "[\(tojson)]" end;
+([], paths) as $p | getpath($p) as $v | [
+    ($p | map(segment) | join("") | if startswith(".") | not then "." + . else . end),
+    ($p[-1] | if type == "string" then . else "" end),
+    ($v | type),
+    ($v | if type == "object" or type == "array" then "" else tostring end)
+] | @tsv
+"#;
+
+pub fn list_nodes(jq: &config::JqConfig, filename: &str) -> Result<Vec<Node>, String> {
+    let mut command = Command::new(&jq.path);
+    command.arg("-r").arg(LIST_NODES).arg(filename);
+    let output = exec(command, None, jq.timeout)?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let nodes = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<String> = line.split('\t').map(unescape_tsv).collect();
+            match fields.as_slice() {
+                [path, key, kind, value] => Some(Node {
+                    path: path.to_string(),
+                    key: key.to_string(),
+                    kind: kind.to_string(),
+                    value: value.to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    Ok(nodes)
+}
+
+fn unescape_tsv(field: &str) -> String {
+    let mut result = String::new();
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Quotes the argument for POSIX shells.
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
 }
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn write_temp(source: &str) -> String {
+fn temp_path(extension: &str) -> PathBuf {
     let mut tmp = env::temp_dir();
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Error figuring out current time");
-    let fname = format!("ijqrs-{}.json", now.as_nanos());
-    tmp.push(fname);
-    let path = tmp.to_str().expect("Error getting temporary file path");
+    tmp.push(format!("ijqrs-{}.{}", now.as_nanos(), extension));
+    tmp
+}
 
-    let mut file = File::create(path).expect("Error creating temp file");
+pub fn write_temp(source: &str) -> Result<String, String> {
+    let tmp = temp_path("json");
+    let mut file = File::create(&tmp).map_err(|e| format!("Error creating temp file: {}", e))?;
     file.write_all(source.as_bytes())
-        .expect("Error writing file!");
-    String::from(path)
+        .map_err(|e| format!("Error writing temp file: {}", e))?;
+    Ok(tmp.to_string_lossy().to_string())
+}
+
+/// Opens the text in `$VISUAL` or `$EDITOR` (vi by default), and returns
+/// it as saved. The terminal is expected to be out of the TUI meanwhile.
+pub fn edit(text: &str, extension: &str) -> Result<String, String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let path = temp_path(extension);
+    fs::write(&path, text).map_err(|e| format!("Error creating temp file: {}", e))?;
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    match status {
+        Ok(status) if status.success() => (),
+        Ok(status) => return Err(format!("{} exited with {}", editor, status)),
+        Err(e) => return Err(format!("Unable to run {}: {}", editor, e)),
+    }
+    edited.map_err(|e| format!("Error reading edited file: {}", e))
 }
@@ -1,43 +1,93 @@
+use super::super::bookmarks;
+use super::super::clipboard;
 use super::super::ui::Pane;
-use super::{app, write_file};
+use super::{app, exec, resolve_path, shell_quote, write_file, write_temp, WriteMode};
+use std::path::PathBuf;
 
-const OUTFILE_CMD: &str = "ijqrs.cmd";
-const OUTFILE_OUT: &str = "ijqrs.out";
+const OUTFILE_BOOKMARKS: &str = "ijqrs-bookmarks.toml";
+const OUTFILE_SHARE: &str = "ijqrs.sh";
+const OUTFILE_SELECTION: &str = "ijqrs.sel";
+const OUTFILE_SCRIPT: &str = "ijqrs-filter.sh";
 
 pub enum Instruction {
     Unknown,
     Jq,
     WriteOut,
     WriteCmd,
-    // YankOut,
-    // YankCmd,
+    BookmarkAdd,
+    Bookmarks,
+    Jump,
+    YankOut,
+    YankCmd,
+    WriteSelection,
+    RunSelection,
+    WriteScript,
+    PipeShow,
+    PipeReplace,
+    Reload,
+    Open,
+    Watch,
 }
 
-pub fn new(inst: Instruction, param: String) -> Box<dyn Instr> {
+pub fn new(inst: Instruction, param: String, force: bool) -> Box<dyn Instr> {
     match inst {
         Instruction::Jq => Box::new(Jq {}),
-        Instruction::WriteOut => Box::new(WriteOut { param }),
-        Instruction::WriteCmd => Box::new(WriteCmd { param }),
+        Instruction::WriteOut => Box::new(WriteOut { param, force }),
+        Instruction::WriteCmd => Box::new(WriteCmd { param, force }),
+        Instruction::BookmarkAdd => Box::new(BookmarkAdd { param }),
+        Instruction::Bookmarks => Box::new(Bookmarks { param, force }),
+        Instruction::Jump => Box::new(Jump { param }),
+        Instruction::YankOut => Box::new(YankOut {}),
+        Instruction::YankCmd => Box::new(YankCmd {}),
+        Instruction::WriteSelection => Box::new(WriteSelection { param, force }),
+        Instruction::RunSelection => Box::new(RunSelection {}),
+        Instruction::WriteScript => Box::new(WriteScript { param, force }),
+        Instruction::Reload => Box::new(Reload {}),
+        Instruction::Open => Box::new(Open { param }),
+        Instruction::Watch => Box::new(Watch { param }),
+        Instruction::PipeShow => Box::new(Pipe {
+            param,
+            replace: false,
+        }),
+        Instruction::PipeReplace => Box::new(Pipe {
+            param,
+            replace: true,
+        }),
         Instruction::Unknown => Box::new(Unknown { param }),
     }
 }
 
 pub trait Instr {
-    fn eval(&self, state: &app::State) -> Result<String, String>;
+    fn eval(&self, state: &mut app::State) -> Result<String, String>;
 }
 
 pub trait InstrWrite {
     fn param(&self) -> String;
-    fn default_filename(&self) -> String;
+    /// Whether to overwrite an existing file.
+    fn force(&self) -> bool;
+    fn default_filename(&self, state: &app::State) -> String;
     fn content(&self, state: &app::State) -> String;
-    fn write(&self, state: &app::State) -> Result<String, String> {
+    /// File name to write to, from param (`>>` to append) or the default.
+    fn target(&self, state: &app::State) -> (String, WriteMode) {
         let cmd = self.param();
-        let mut fname = self.default_filename();
-        if !cmd.is_empty() {
-            fname = cmd;
+        let mut mode = match self.force() {
+            true => WriteMode::Overwrite,
+            false => WriteMode::Create,
+        };
+        let mut fname = cmd.trim();
+        if let Some(target) = fname.strip_prefix(">>") {
+            mode = WriteMode::Append;
+            fname = target.trim();
         }
-        let fname = fname;
-        Ok(write_file(&fname, &self.content(state)))
+        let fname = match fname.is_empty() {
+            true => self.default_filename(state),
+            false => fname.to_string(),
+        };
+        (fname, mode)
+    }
+    fn write(&self, state: &app::State) -> Result<String, String> {
+        let (fname, mode) = self.target(state);
+        write_file(&fname, &self.content(state), mode)
     }
 }
 
@@ -45,8 +95,8 @@ impl<T> Instr for T
 where
     T: InstrWrite,
 {
-    fn eval(&self, state: &app::State) -> Result<String, String> {
-        self.write(state)
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        self.write(&*state)
     }
 }
 
@@ -54,56 +104,394 @@ struct Unknown {
     param: String,
 }
 impl Instr for Unknown {
-    fn eval(&self, _state: &app::State) -> Result<String, String> {
+    fn eval(&self, _state: &mut app::State) -> Result<String, String> {
         Err(format!("Unknown command: {}", self.param))
     }
 }
 
+struct Jump {
+    param: String,
+}
+impl Instr for Jump {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        let line: usize = self
+            .param
+            .parse()
+            .map_err(|_| format!("Invalid line number: {}", self.param))?;
+        state.get_mut_content().scroll_to(line.saturating_sub(1));
+        Ok(String::from(""))
+    }
+}
+
+use std::fs;
 use std::process::Command;
 struct Jq;
 impl Instr for Jq {
-    fn eval(&self, state: &app::State) -> Result<String, String> {
-        let command = &state.jq().get_content();
-        let filename = state.filename.as_str();
-        let command = Command::new("jq")
-            .arg(command)
-            .arg(filename)
-            .output()
-            .expect("Command execution failed");
-        let result = String::from_utf8(command.stdout).expect("Invalid stdout");
-        if result.is_empty() {
-            return Ok(String::from_utf8(command.stderr).expect("Invalid stderr"));
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        run_jq(state, &state.filename)
+    }
+}
+
+fn run_jq(state: &app::State, filename: &str) -> Result<String, String> {
+    let command = &state.jq().value();
+    let mut jq = Command::new(&state.config.jq.path);
+    jq.args(&state.options).arg(command).arg(filename);
+    let command = exec(jq, None, state.config.jq.timeout)?;
+    let result = String::from_utf8_lossy(&command.stdout).to_string();
+    if result.is_empty() {
+        let stderr = String::from_utf8_lossy(&command.stderr).to_string();
+        if !command.status.success() {
+            return Err(stderr);
         }
+        return Ok(stderr);
+    }
+    Ok(result)
+}
+
+struct RunSelection;
+impl Instr for RunSelection {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        let selected = match state.get_content().selected() {
+            Some(selected) => selected,
+            None => return Err(String::from("Nothing selected")),
+        };
+        let tmp = write_temp(&selected)?;
+        let result = run_jq(state, &tmp);
+        let _ = fs::remove_file(tmp);
+        let result = result?;
+        state.output.replace(result.to_string());
+        state.result_filter = None;
         Ok(result)
     }
 }
 
+struct Reload;
+impl Instr for Reload {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        state.reload()?;
+        Ok(state.filename.to_string())
+    }
+}
+
+/// Opens another file, or the file browser for a directory. Reloads the
+/// current file without param.
+struct Open {
+    param: String,
+}
+impl Instr for Open {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        let fname = self.param.trim();
+        if fname.is_empty() {
+            return Reload {}.eval(state);
+        }
+        let path = match fname.starts_with('~') {
+            true => resolve_path(fname)?,
+            false => PathBuf::from(fname),
+        };
+        if path.is_dir() {
+            state.browse(&path)?;
+            return Ok(String::from(""));
+        }
+        let fname = path.display().to_string();
+        state.open(&fname)?;
+        Ok(fname)
+    }
+}
+
+/// Turns watching the source file on or off, toggles it without param.
+struct Watch {
+    param: String,
+}
+impl Instr for Watch {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        state.watch = match self.param.trim() {
+            "" => !state.watch,
+            "on" => true,
+            "off" => false,
+            other => return Err(format!("Unknown watch mode: {}", other)),
+        };
+        Ok(String::from(""))
+    }
+}
+
+/// Pipes the result through a shell command, showing what it prints or
+/// replacing the result with it.
+struct Pipe {
+    param: String,
+    replace: bool,
+}
+impl Instr for Pipe {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        let shell = self.param.trim();
+        if shell.is_empty() {
+            return Err(String::from("Missing shell command"));
+        }
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(shell);
+        let input = state.output.get_content();
+        let output = exec(command, Some(&input), state.config.jq.timeout)?;
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if !output.status.success() {
+            let code = match output.status.code() {
+                Some(code) => code.to_string(),
+                None => String::from("signal"),
+            };
+            let mut msg = format!("{} exited with {}", shell, code);
+            if !stderr.trim().is_empty() {
+                msg = format!("{}: {}", msg, stderr.trim());
+            }
+            return Err(msg);
+        }
+        if !self.replace {
+            state.show_popup(shell, format!("{}{}", stdout, stderr));
+            return Ok(stdout);
+        }
+        state.output.replace(stdout.to_string());
+        state.result_filter = None;
+        if !stderr.trim().is_empty() {
+            // Shown in the status, the result being replaced all the same.
+            return Err(format!("{}: {}", shell, stderr.trim()));
+        }
+        Ok(stdout)
+    }
+}
+
 struct WriteOut {
     param: String,
+    force: bool,
 }
 impl InstrWrite for WriteOut {
     fn param(&self) -> String {
         self.param.as_str().to_string()
     }
-    fn default_filename(&self) -> String {
-        String::from(OUTFILE_OUT)
+    fn force(&self) -> bool {
+        self.force
+    }
+    fn default_filename(&self, state: &app::State) -> String {
+        state.config.output.result.to_string()
     }
     fn content(&self, state: &app::State) -> String {
         state.output.get_content()
     }
 }
 
+struct WriteSelection {
+    param: String,
+    force: bool,
+}
+impl InstrWrite for WriteSelection {
+    fn param(&self) -> String {
+        self.param.as_str().to_string()
+    }
+    fn force(&self) -> bool {
+        self.force
+    }
+    fn default_filename(&self, _state: &app::State) -> String {
+        OUTFILE_SELECTION.to_string()
+    }
+    fn content(&self, state: &app::State) -> String {
+        let content = state.get_content();
+        content.selected().unwrap_or_else(|| content.get_content())
+    }
+}
+
+/// Writes the filter, with the jq options, as an executable shell script
+/// or, for a `.jq` file name, as a jq module to use with `jq -f`.
+struct WriteScript {
+    param: String,
+    force: bool,
+}
+impl WriteScript {
+    fn is_module(&self, state: &app::State) -> bool {
+        self.target(state).0.ends_with(".jq")
+    }
+}
+impl InstrWrite for WriteScript {
+    fn param(&self) -> String {
+        self.param.as_str().to_string()
+    }
+    fn force(&self) -> bool {
+        self.force
+    }
+    fn default_filename(&self, _state: &app::State) -> String {
+        OUTFILE_SCRIPT.to_string()
+    }
+    fn content(&self, state: &app::State) -> String {
+        let filter = state.composed_filter();
+        let options: Vec<String> = state.options.iter().map(|o| shell_quote(o)).collect();
+        let input = shell_quote(state.origin());
+        if self.is_module(state) {
+            let mut usage = vec![String::from("jq")];
+            usage.extend(options);
+            usage.push(String::from("-f"));
+            usage.push(shell_quote(&self.target(state).0));
+            usage.push(input);
+            return format!(
+                "# jq filter saved by ijqrs.\n# Usage: {}\n\n{}\n",
+                usage.join(" "),
+                filter
+            );
+        }
+        let mut command = vec![String::from("jq")];
+        command.extend(options);
+        command.push(shell_quote(&filter));
+        command.push(String::from("\"$input\""));
+        format!(
+            "#!/bin/sh\n# jq filter saved by ijqrs.\n# Usage: $0 [FILE_NAME], reads {} by default.\n\ninput=${{1:-{}}}\n{}\n",
+            input,
+            input,
+            command.join(" ")
+        )
+    }
+    fn write(&self, state: &app::State) -> Result<String, String> {
+        let (fname, mode) = self.target(state);
+        let path = write_file(&fname, &self.content(state), mode)?;
+        if !self.is_module(state) {
+            make_executable(&path)?;
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &str) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("Unable to make {} executable: {}", path, e))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &str) -> Result<(), String> {
+    Ok(())
+}
+
 struct WriteCmd {
     param: String,
+    force: bool,
 }
 impl InstrWrite for WriteCmd {
     fn param(&self) -> String {
         self.param.as_str().to_string()
     }
-    fn default_filename(&self) -> String {
-        String::from(OUTFILE_CMD)
+    fn force(&self) -> bool {
+        self.force
+    }
+    fn default_filename(&self, state: &app::State) -> String {
+        state.config.output.command.to_string()
+    }
+    fn content(&self, state: &app::State) -> String {
+        state.composed_filter()
+    }
+}
+
+struct YankOut;
+impl Instr for YankOut {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        clipboard::copy(&state.output.get_content())
+    }
+}
+
+struct YankCmd;
+impl Instr for YankCmd {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        clipboard::copy(&state.composed_filter())
+    }
+}
+
+struct BookmarkAdd {
+    param: String,
+}
+impl Instr for BookmarkAdd {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        state.add_named_bookmark(&self.param);
+        Ok(state.jq().value())
+    }
+}
+
+struct Bookmarks {
+    param: String,
+    force: bool,
+}
+impl Bookmarks {
+    fn import(&self, fname: &str, state: &mut app::State) -> Result<String, String> {
+        if fname.is_empty() {
+            return Err(String::from("Missing bookmarks file to import"));
+        }
+        let path = resolve_path(fname)?;
+        let bms = bookmarks::unbundle(&path.to_string_lossy())?;
+        let (added, conflicts) = state.bookmarks.merge(bms);
+        if !conflicts.is_empty() {
+            return Err(format!(
+                "Imported {} bookmark(s), skipped conflicting: {}",
+                added,
+                conflicts.join(", ")
+            ));
+        }
+        let msg = format!("Imported {} bookmark(s)", added);
+        state.set_info(&msg);
+        Ok(msg)
+    }
+}
+
+/// Writes all the bookmarks to a bundle.
+struct BookmarksExport {
+    param: String,
+    force: bool,
+}
+impl InstrWrite for BookmarksExport {
+    fn param(&self) -> String {
+        self.param.as_str().to_string()
+    }
+    fn force(&self) -> bool {
+        self.force
+    }
+    fn default_filename(&self, _state: &app::State) -> String {
+        OUTFILE_BOOKMARKS.to_string()
     }
     fn content(&self, state: &app::State) -> String {
-        state.jq().get_content()
+        bookmarks::bundle(state.bookmarks.items())
+    }
+}
+
+/// Writes the current filter as a standalone jq invocation.
+struct BookmarksShare {
+    param: String,
+    force: bool,
+}
+impl InstrWrite for BookmarksShare {
+    fn param(&self) -> String {
+        self.param.as_str().to_string()
+    }
+    fn force(&self) -> bool {
+        self.force
+    }
+    fn default_filename(&self, _state: &app::State) -> String {
+        OUTFILE_SHARE.to_string()
+    }
+    fn content(&self, state: &app::State) -> String {
+        let mut line = vec![String::from("jq")];
+        for option in &state.options {
+            line.push(shell_quote(option));
+        }
+        line.push(shell_quote(&state.composed_filter()));
+        line.push(shell_quote(state.origin()));
+        format!("{}\n", line.join(" "))
+    }
+}
+
+impl Instr for Bookmarks {
+    fn eval(&self, state: &mut app::State) -> Result<String, String> {
+        let (action, fname) = match self.param.trim().split_once(' ') {
+            Some((action, fname)) => (action, fname.trim()),
+            None => (self.param.trim(), ""),
+        };
+        let (param, force) = (fname.to_string(), self.force);
+        match action {
+            "export" => BookmarksExport { param, force }.eval(state),
+            "import" => self.import(fname, state),
+            "share" => BookmarksShare { param, force }.eval(state),
+            _ => Err(format!("Unknown bookmarks action: {}", action)),
+        }
     }
 }
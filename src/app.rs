@@ -1,75 +1,167 @@
 use std::{
     fs,
     io::{self, BufRead},
+    path::Path,
+    time::SystemTime,
 };
 
 use super::actions;
+use super::bookmarks;
+use super::clipboard;
+use super::config;
+use super::keymap;
 use super::ui;
 use super::ui::Pane;
 
 pub enum Signal {
     Quit,
     Nop,
+    Resize,
     Run,
     AddBookmark,
+    UpdateBookmark,
     LoadBookmark(String),
+    Search,
+    UseHit,
+    Yank(String),
+    Edit,
+    Reload,
+    Open(String),
+    Complete,
 }
 
 pub enum Mode {
     Internal,
     Shell,
     Bookmarks,
+    Browser,
     Help,
 }
 
 pub enum Status {
     Error(String),
+    Info(String),
     Ok,
 }
 
+/// Source and filter drilled down from, the result of which became the source.
+pub struct Level {
+    pub filename: String,
+    pub filter: String,
+}
+
 pub struct State {
     pub output: ui::panels::Content,
     pub source: ui::panels::Content,
     pub bookmarks: ui::panels::Bookmarks,
+    pub browser: ui::panels::Browser,
     pub filename: String,
+    pub options: Vec<String>,
+    pub config: config::Config,
+    pub theme: ui::Theme,
+    pub keymap: keymap::Keymap,
+    /// Title and text shown over the panels, until dismissed.
+    pub popup: Option<(String, ui::panels::Content)>,
+    /// Whether to reload the source when the file changes.
+    pub watch: bool,
+    /// Levels drilled down from, the original file first.
+    pub levels: Vec<Level>,
+    /// The filter the result came from, unset when the result is an error
+    /// or was changed by another command.
+    pub result_filter: Option<String>,
 
     command: ui::panels::Command,
     internal: ui::panels::Command,
     active: ui::Panel,
+    last_content: ui::Panel,
+    modified: Option<SystemTime>,
     mode: Mode,
 }
 
 impl State {
     pub fn from_file(filename: &str) -> State {
-        let source = fs::read_to_string(filename).expect("Error reading file");
-        State::new(filename, &source)
+        match read_source(filename) {
+            Ok(source) => State::new(filename, &source),
+            Err(msg) => {
+                let mut state = State::new(filename, "");
+                state.command.set_error(&msg);
+                state
+            }
+        }
     }
     pub fn from_stdin() -> State {
         let mut source = String::from("");
         for line in io::stdin().lock().lines() {
             source += (line.expect("IO Error") + "\n").as_str();
         }
-        let filename = actions::write_temp(&source);
-        State::new(&filename, &source)
+        match actions::write_temp(&source) {
+            Ok(filename) => State::new(&filename, &source),
+            Err(msg) => {
+                let mut state = State::new("", &source);
+                state.command.set_error(&msg);
+                state
+            }
+        }
     }
 
     fn new(filename: &str, source: &str) -> State {
-        let command = ui::panels::Command::new(String::from(".|keys"));
+        let mut errors = Vec::new();
+        let config = config::load().unwrap_or_else(|e| {
+            errors.push(e);
+            config::Config::default()
+        });
+        let theme = ui::Theme::from_config(&config.theme).unwrap_or_else(|e| {
+            errors.push(format!("Invalid theme: {}", e));
+            ui::Theme::default()
+        });
+        let keymap = keymap::Keymap::from_config(&config.keys).unwrap_or_else(|e| {
+            errors.push(e);
+            keymap::Keymap::default()
+        });
+        let mut options = config.jq.options.to_vec();
+        match config::get_jq_options() {
+            Ok(project) => options.extend(project),
+            Err(e) => errors.push(e),
+        }
+
+        let command = ui::panels::Command::with_history(
+            config.filter.to_string(),
+            config::get_history_file_path(),
+        );
         let internal = ui::panels::Command::new(String::from(""));
-        let bookmarks = ui::panels::Bookmarks::new();
+        let mut bookmarks = ui::panels::Bookmarks::new();
+        bookmarks.set_filename(filename);
+        if let Some(msg) = bookmarks.take_error() {
+            errors.push(msg);
+        }
 
+        let watch = config.watch;
         let mut state = State {
             filename: String::from(filename),
+            options,
+            config,
+            theme,
+            keymap,
+            popup: None,
+            watch,
+            levels: Vec::new(),
+            result_filter: None,
+            modified: get_modified(filename),
             source: ui::panels::Content::new(String::from(source), ui::Panel::Source),
             output: ui::panels::Content::new(String::from(""), ui::Panel::Output),
 
             command,
             internal,
             bookmarks,
+            browser: ui::panels::Browser::new(),
             active: ui::Panel::Command,
+            last_content: ui::Panel::Output,
             mode: Mode::Shell,
         };
         state.run_shell_command();
+        if !errors.is_empty() {
+            state.command.set_error(&errors.join("; "));
+        }
         state
     }
 
@@ -83,6 +175,7 @@ impl State {
             Mode::Internal => self.set_mode(Mode::Shell),
             Mode::Help => self.set_mode(Mode::Shell),
             Mode::Bookmarks => self.set_mode(Mode::Shell),
+            Mode::Browser => self.set_mode(Mode::Shell),
         };
     }
 
@@ -96,6 +189,7 @@ impl State {
             Mode::Shell => &self.command,
             Mode::Help => &self.command,
             Mode::Bookmarks => &self.command,
+            Mode::Browser => &self.command,
         }
     }
 
@@ -104,6 +198,12 @@ impl State {
     }
 
     pub fn get_mut_active(&mut self) -> Box<&mut dyn ui::Pane> {
+        if let Mode::Bookmarks = self.mode {
+            return Box::new(&mut self.bookmarks);
+        }
+        if let Mode::Browser = self.mode {
+            return Box::new(&mut self.browser);
+        }
         if ui::Panel::Source == self.active {
             return Box::new(&mut self.source);
         }
@@ -115,10 +215,17 @@ impl State {
             Mode::Shell => Box::new(&mut self.command),
             Mode::Help => Box::new(&mut self.command),
             Mode::Bookmarks => Box::new(&mut self.bookmarks),
+            Mode::Browser => Box::new(&mut self.browser),
         }
     }
 
     pub fn get_active(&self) -> Box<&dyn ui::Pane> {
+        if let Mode::Bookmarks = self.mode {
+            return Box::new(&self.bookmarks);
+        }
+        if let Mode::Browser = self.mode {
+            return Box::new(&self.browser);
+        }
         if ui::Panel::Source == self.active {
             return Box::new(&self.source);
         }
@@ -129,21 +236,43 @@ impl State {
     }
 
     pub fn set_active(&mut self, active: ui::Panel) {
+        match active {
+            ui::Panel::Source => self.last_content = ui::Panel::Source,
+            ui::Panel::Output => self.last_content = ui::Panel::Output,
+            _ => (),
+        }
         self.active = active;
     }
 
+    /// Content panel that was focused last, Result by default.
+    pub fn get_content(&self) -> &ui::panels::Content {
+        match self.last_content {
+            ui::Panel::Source => &self.source,
+            _ => &self.output,
+        }
+    }
+
+    pub fn get_mut_content(&mut self) -> &mut ui::panels::Content {
+        match self.last_content {
+            ui::Panel::Source => &mut self.source,
+            _ => &mut self.output,
+        }
+    }
+
     pub fn run_current_command(&mut self) {
         match self.mode {
             Mode::Shell => self.run_shell_command(),
             Mode::Internal => self.run_internal_command(),
             Mode::Help => (),
             Mode::Bookmarks => (),
+            Mode::Browser => (),
         }
     }
 
     pub fn run_internal_command(&mut self) {
         self.internal.record();
-        let result = actions::run(&self.internal.get_content(), self);
+        let command = self.internal.value();
+        let result = actions::run(&command, self);
         self.internal.clear();
         if let Err(msg) = result {
             self.internal.set_error(&msg);
@@ -152,21 +281,328 @@ impl State {
 
     pub fn run_shell_command(&mut self) {
         self.command.record();
-        let output = match actions::run(actions::RUN, self) {
-            Ok(result) => result,
-            Err(result) => result,
+        let output = self.run_filter();
+        self.output.replace(output);
+    }
+
+    fn run_filter(&mut self) -> String {
+        match actions::run(actions::RUN, self) {
+            Ok(result) => {
+                self.result_filter = Some(self.command.value());
+                result
+            }
+            Err(result) => {
+                self.result_filter = None;
+                result
+            }
+        }
+    }
+
+    pub fn source_changed(&self) -> bool {
+        get_modified(&self.filename) != self.modified
+    }
+
+    /// Reads the source file again and re-runs the filter, keeping the
+    /// scroll positions where possible.
+    pub fn reload(&mut self) -> Result<(), String> {
+        self.modified = get_modified(&self.filename);
+        let source = read_source(&self.filename)?;
+        self.source.reload(source);
+        let output = self.run_filter();
+        self.output.reload(output);
+        Ok(())
+    }
+
+    pub fn reload_source(&mut self) {
+        if let Err(msg) = self.reload() {
+            self.command.set_error(&msg);
+        }
+    }
+
+    /// Replaces the source with the file, and runs the filter against it.
+    pub fn open(&mut self, filename: &str) -> Result<(), String> {
+        let current = self.filename.to_string();
+        self.load(filename)?;
+        self.bookmarks.set_filename(filename);
+        self.drop_levels(&current);
+        Ok(())
+    }
+
+    /// Forgets the levels drilled down through, removing their temporary files.
+    fn drop_levels(&mut self, current: &str) {
+        if self.levels.is_empty() {
+            return;
+        }
+        let _ = fs::remove_file(current);
+        for level in self.levels.drain(..).skip(1) {
+            let _ = fs::remove_file(level.filename);
+        }
+    }
+
+    pub fn close(&mut self) {
+        let current = self.filename.to_string();
+        self.drop_levels(&current);
+    }
+
+    fn load(&mut self, filename: &str) -> Result<(), String> {
+        let source = read_source(filename)?;
+        self.filename = String::from(filename);
+        self.modified = get_modified(filename);
+        self.source.replace(source);
+        self.run_shell_command();
+        Ok(())
+    }
+
+    /// Makes the result the new source, to be filtered further.
+    pub fn drill_down(&mut self) {
+        let result = self.output.get_content();
+        if result.trim().is_empty() {
+            return self.command.set_error("No result to drill down into");
+        }
+        let filter = match &self.result_filter {
+            Some(filter) => filter.to_string(),
+            None => {
+                return self
+                    .command
+                    .set_error("The result didn't come from the filter, run it first")
+            }
+        };
+        let filename = match actions::write_temp(&result) {
+            Ok(filename) => filename,
+            Err(msg) => return self.command.set_error(&msg),
+        };
+        self.levels.push(Level {
+            filename: self.filename.to_string(),
+            filter,
+        });
+        self.command.replace(String::from("."));
+        if let Err(msg) = self.load(&filename) {
+            self.command.set_error(&msg);
+        }
+    }
+
+    /// Goes back to the source and filter drilled down from.
+    pub fn drill_up(&mut self) {
+        let level = match self.levels.pop() {
+            Some(level) => level,
+            None => return,
+        };
+        let _ = fs::remove_file(&self.filename);
+        self.command.replace(level.filter);
+        if let Err(msg) = self.load(&level.filename) {
+            self.command.set_error(&msg);
+        }
+    }
+
+    /// The file drilled down from first, or the current one.
+    pub fn origin(&self) -> &str {
+        match self.levels.first() {
+            Some(level) => &level.filename,
+            None => &self.filename,
+        }
+    }
+
+    /// The filters drilled down through and the current one, as one filter
+    /// to run against the original file.
+    pub fn composed_filter(&self) -> String {
+        let filter = self.command.value();
+        if self.levels.is_empty() {
+            return filter;
+        }
+        let mut filters: Vec<String> = self
+            .levels
+            .iter()
+            .map(|level| format!("({})", end_line(level.filter.trim())))
+            .collect();
+        filters.push(format!("({})", end_line(filter.trim())));
+        filters.join(" | ")
+    }
+
+    /// The original file name and the filters drilled down through.
+    pub fn breadcrumbs(&self) -> Vec<String> {
+        let mut crumbs = vec![self.origin().to_string()];
+        crumbs.extend(self.levels.iter().map(|level| level.filter.to_string()));
+        crumbs
+    }
+
+    /// Opens the file picked in the file browser.
+    pub fn open_file(&mut self, filename: &str) {
+        match self.open(filename) {
+            Ok(_) => self.set_mode(Mode::Shell),
+            Err(msg) => self.command.set_error(&msg),
+        }
+    }
+
+    /// Shows the file browser, listing the directory.
+    pub fn browse(&mut self, dir: &Path) -> Result<(), String> {
+        self.browser.open(dir)?;
+        self.set_mode(Mode::Browser);
+        self.set_active(ui::Panel::Command);
+        Ok(())
+    }
+
+    /// Completes the file name of the `:e` internal command, listing the
+    /// candidates when there's nothing left to complete.
+    pub fn complete(&mut self) {
+        if !matches!(self.mode, Mode::Internal) {
+            return;
+        }
+        let command = self.internal.value();
+        let (instruction, partial) = match command.split_once(' ') {
+            Some(split) => split,
+            None => return,
         };
-        self.output = ui::panels::Content::new(output, ui::Panel::Output);
+        if instruction.trim_start_matches(':') != actions::EDIT {
+            return;
+        }
+        let candidates = actions::complete_path(partial);
+        let common = common_prefix(&candidates);
+        if common.len() > partial.len() {
+            self.internal.replace(format!("{} {}", instruction, common));
+        } else if candidates.len() > 1 {
+            let dir = partial.rfind('/').map(|idx| idx + 1).unwrap_or(0);
+            let names: Vec<&str> = candidates.iter().map(|c| &c[dir..]).collect();
+            self.show_popup("Completions", names.join("\n"));
+        }
+    }
+
+    pub fn show_popup(&mut self, title: &str, text: String) {
+        let content = ui::panels::Content::new(text, ui::Panel::Popup);
+        self.popup = Some((title.to_string(), content));
+    }
+
+    /// Reports the outcome of an internal command in its status.
+    pub fn set_info(&mut self, msg: &str) {
+        self.internal.set_info(msg);
+    }
+
+    /// Shows the error of the last bookmarks change, if any, in the status.
+    pub fn check_bookmarks(&mut self) {
+        if let Some(msg) = self.bookmarks.take_error() {
+            self.command.set_error(&msg);
+        }
     }
 
     pub fn add_bookmark(&mut self) {
-        let cmd = self.command.get_content();
+        let cmd = self.command.value();
         self.bookmarks.add(&cmd);
     }
 
+    pub fn add_named_bookmark(&mut self, meta: &str) {
+        let cmd = self.command.value();
+        self.bookmarks
+            .insert(bookmarks::Bookmark::from_meta(&cmd, meta));
+    }
+
+    pub fn update_bookmark(&mut self) {
+        let cmd = self.command.value();
+        self.bookmarks.update_current_item(&cmd);
+    }
+
+    /// Lists the JSON nodes of the focused content panel for the structural search.
+    pub fn search_nodes(&mut self) {
+        let nodes = match self.last_content {
+            ui::Panel::Source => actions::list_nodes(&self.config.jq, &self.filename),
+            _ => match actions::write_temp(&self.output.get_content()) {
+                Ok(tmp) => {
+                    let nodes = actions::list_nodes(&self.config.jq, &tmp);
+                    let _ = fs::remove_file(tmp);
+                    nodes
+                }
+                Err(msg) => Err(msg),
+            },
+        };
+        match nodes {
+            Ok(nodes) => self.get_mut_content().set_nodes(nodes),
+            Err(msg) => self.command.set_error(&msg),
+        }
+    }
+
+    /// Runs the path of the current structural search hit, as the filter.
+    pub fn use_hit(&mut self) {
+        let path = match self.get_mut_content().current_hit() {
+            Some(hit) => hit.path.to_string(),
+            None => return,
+        };
+        let filter = match self.last_content {
+            ui::Panel::Source => path,
+            _ => format!("{} | {}", end_line(&self.command.value()), path),
+        };
+        self.set_filter(filter);
+        self.set_active(ui::Panel::Command);
+    }
+
+    /// Replaces the filter and runs it.
+    pub fn set_filter(&mut self, filter: String) {
+        self.command.replace(filter);
+        self.run_shell_command();
+    }
+
+    /// Loads the edited filter back and runs it; edited content is dropped.
+    pub fn apply_edit(&mut self, edited: Result<String, String>, filter: bool) {
+        match edited {
+            Ok(text) if filter => self.set_filter(text.trim_end_matches('\n').to_string()),
+            Ok(_) => (),
+            Err(msg) => self.command.set_error(&msg),
+        }
+    }
+
+    /// Text to open in the editor: the focused content panel, or the filter.
+    pub fn get_editable(&self) -> (String, bool) {
+        match (&self.mode, &self.active) {
+            (Mode::Shell | Mode::Internal, ui::Panel::Source | ui::Panel::Output) => {
+                (self.get_content().get_content(), false)
+            }
+            _ => (self.command.value(), true),
+        }
+    }
+
+    pub fn yank(&mut self, text: &str) {
+        if let Err(msg) = clipboard::copy(text) {
+            self.command.set_error(&msg);
+        }
+    }
+
     pub fn load_bookmark(&mut self, bm: String) {
         self.command.replace(bm);
         self.command.record();
         self.switch_mode();
     }
 }
+
+fn read_source(filename: &str) -> Result<String, String> {
+    fs::read_to_string(filename).map_err(|e| format!("Error reading {}: {}", filename, e))
+}
+
+/// The filter, with a line break after it when its last line may end in a
+/// comment, so that more can be put after it.
+fn end_line(filter: &str) -> String {
+    match filter.lines().last() {
+        Some(line) if line.contains('#') => format!("{}\n", filter),
+        _ => filter.to_string(),
+    }
+}
+
+/// Longest prefix shared by all the strings.
+fn common_prefix(strings: &[String]) -> String {
+    let first = match strings.first() {
+        Some(first) => first,
+        None => return String::from(""),
+    };
+    let mut len = first.len();
+    for other in &strings[1..] {
+        len = first
+            .char_indices()
+            .zip(other.chars())
+            .take_while(|((_, a), b)| a == b)
+            .map(|((idx, a), _)| idx + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+fn get_modified(filename: &str) -> Option<SystemTime> {
+    fs::metadata(filename).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,52 @@
+use std::process::Command;
+
+use super::bookmarks;
+use super::config;
+use super::opts::Batch;
+
+/// Exit code for errors running jq at all, as opposed to jq's own.
+pub const EXIT_ERROR: i32 = 2;
+
+/// Runs the named bookmark, or the filter, against the file (or STDIN),
+/// with jq writing the result to STDOUT. Returns jq's exit code.
+pub fn run(batch: &Batch) -> i32 {
+    match execute(batch) {
+        Ok(code) => code,
+        Err(msg) => {
+            eprintln!("ijqrs: {}", msg);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn execute(batch: &Batch) -> Result<i32, String> {
+    let config = config::load()?;
+    let filename = batch.filename.as_deref();
+    let filter = find_bookmark(&batch.target, filename).unwrap_or_else(|| batch.target.to_string());
+
+    let mut jq = Command::new(&config.jq.path);
+    jq.args(&config.jq.options)
+        .args(config::get_jq_options()?)
+        .args(batch.format.options())
+        .arg(filter)
+        .args(filename);
+    let status = jq
+        .status()
+        .map_err(|e| format!("Unable to run {}: {}", config.jq.path, e))?;
+    Ok(status.code().unwrap_or(EXIT_ERROR))
+}
+
+/// Filter of the bookmark with the name, preferring the ones scoped to the file.
+fn find_bookmark(name: &str, filename: Option<&str>) -> Option<String> {
+    let (bms, errors) = bookmarks::load();
+    for msg in errors {
+        eprintln!("ijqrs: {}", msg);
+    }
+    let mut named = bms
+        .iter()
+        .filter(|bm| !bm.name.is_empty() && bm.name == name);
+    let scoped = filename.and_then(|filename| named.clone().find(|bm| bm.applies_to(filename)));
+    scoped
+        .or_else(|| named.next())
+        .map(|bm| bm.filter.to_string())
+}
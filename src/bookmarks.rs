@@ -0,0 +1,312 @@
+use super::config::{get_config_path, get_project_path};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BOOKMARKS_FILE: &str = "bookmarks.toml";
+const LEGACY_BOOKMARKS_FILE: &str = "bookmarks";
+const LEGACY_BACKUP_FILE: &str = "bookmarks.old";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Origin {
+    #[default]
+    User,
+    Project,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bookmark {
+    pub name: String,
+    pub filter: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub created: u64,
+    pub last_used: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    pub pinned: bool,
+    #[serde(skip)]
+    pub origin: Origin,
+}
+
+impl Bookmark {
+    pub fn new(filter: &str) -> Bookmark {
+        Bookmark {
+            filter: filter.to_string(),
+            created: now(),
+            ..Default::default()
+        }
+    }
+
+    /// Parses bookmark meta data in the `NAME #tag @scope -- description` form.
+    pub fn from_meta(filter: &str, meta: &str) -> Bookmark {
+        let mut bm = Bookmark::new(filter);
+        let (words, description) = match meta.split_once("--") {
+            Some((words, description)) => (words, description.trim()),
+            None => (meta, ""),
+        };
+        let mut name = Vec::new();
+        for word in words.split_whitespace() {
+            if let Some(tag) = word.strip_prefix('#') {
+                bm.tags.push(tag.to_string());
+            } else if let Some(scope) = word.strip_prefix('@') {
+                bm.scope = Some(scope.to_string());
+            } else {
+                name.push(word);
+            }
+        }
+        bm.name = name.join(" ");
+        bm.description = description.to_string();
+        bm
+    }
+
+    pub fn touch(&mut self) {
+        self.last_used = now();
+    }
+
+    pub fn applies_to(&self, filename: &str) -> bool {
+        match &self.scope {
+            None => true,
+            Some(pattern) => {
+                let base = Path::new(filename)
+                    .file_name()
+                    .and_then(|f| f.to_str())
+                    .unwrap_or(filename);
+                glob_match(pattern, filename) || glob_match(pattern, base)
+            }
+        }
+    }
+
+    /// Pin and origin markers, shown before the label.
+    pub fn markers(&self) -> String {
+        let origin = match self.origin {
+            Origin::User => "[user]",
+            Origin::Project => "[project]",
+        };
+        match self.pinned {
+            true => format!("* {} ", origin),
+            false => format!("{} ", origin),
+        }
+    }
+
+    /// Name, filter, tags and description, the text searched in the overlay.
+    pub fn label(&self) -> String {
+        let filter = self.filter.replace('\n', " ");
+        let mut label = match self.name.is_empty() {
+            true => filter,
+            false => format!("{}: {}", self.name, filter),
+        };
+        for tag in &self.tags {
+            label.push_str(&format!(" #{}", tag));
+        }
+        if !self.description.is_empty() {
+            label.push_str(&format!(" -- {}", self.description));
+        }
+        label
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Store {
+    #[serde(default)]
+    bookmark: Vec<Bookmark>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Error figuring out current time")
+        .as_secs()
+}
+
+/// Matches `*` and `?` wildcards, the rest is compared literally.
+fn glob_match(pattern: &str, subject: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let subject: Vec<char> = subject.chars().collect();
+    let (mut p, mut s) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while s < subject.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == subject[s]) {
+            p += 1;
+            s += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, s));
+            p += 1;
+        } else if let Some((bp, bs)) = backtrack {
+            p = bp + 1;
+            s = bs + 1;
+            backtrack = Some((bp, bs + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn get_bookmarks_file_path() -> Result<PathBuf, String> {
+    let path = get_config_path().join(BOOKMARKS_FILE);
+    if !path.exists() {
+        migrate_legacy_bookmarks(&path)?;
+    }
+    Ok(path)
+}
+
+/// Converts the old one-filter-per-line bookmarks file, keeping it as a backup.
+fn migrate_legacy_bookmarks(path: &Path) -> Result<(), String> {
+    let legacy = get_config_path().join(LEGACY_BOOKMARKS_FILE);
+    let mut bms = Vec::new();
+    if legacy.exists() {
+        let raw = std::fs::read_to_string(&legacy)
+            .map_err(|e| format!("Unable to read {}: {}", legacy.display(), e))?;
+        for line in raw.split('\n') {
+            if !line.is_empty() {
+                bms.push(Bookmark::new(line));
+            }
+        }
+    }
+    write_bookmarks(path, &bms)?;
+    if legacy.exists() {
+        std::fs::rename(&legacy, get_config_path().join(LEGACY_BACKUP_FILE))
+            .map_err(|e| format!("Unable to back up {}: {}", legacy.display(), e))?;
+    }
+    Ok(())
+}
+
+pub fn has_project() -> bool {
+    get_project_path().is_some()
+}
+
+/// Loads the user bookmarks, followed by the ones from the project directory.
+/// The files that can't be read are skipped, with their errors returned.
+pub fn load() -> (Vec<Bookmark>, Vec<String>) {
+    let mut bms = Vec::new();
+    let mut errors = Vec::new();
+    match get_bookmarks_file_path().and_then(|path| read_bookmarks(&path, Origin::User)) {
+        Ok(user) => bms.extend(user),
+        Err(msg) => errors.push(msg),
+    }
+    if let Some(project) = get_project_path() {
+        let path = project.join(BOOKMARKS_FILE);
+        if path.exists() {
+            match read_bookmarks(&path, Origin::Project) {
+                Ok(project) => bms.extend(project),
+                Err(msg) => errors.push(msg),
+            }
+        }
+    }
+    (bms, errors)
+}
+
+pub fn save(bms: &[Bookmark]) -> Result<(), String> {
+    let mut errors = Vec::new();
+    let user = get_bookmarks_file_path()
+        .and_then(|path| write_bookmarks(&path, &by_origin(bms, Origin::User)));
+    if let Err(msg) = user {
+        errors.push(msg);
+    }
+    if let Some(project) = get_project_path() {
+        let path = project.join(BOOKMARKS_FILE);
+        let project_bms = by_origin(bms, Origin::Project);
+        if path.exists() || !project_bms.is_empty() {
+            if let Err(msg) = write_bookmarks(&path, &project_bms) {
+                errors.push(msg);
+            }
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("; ")),
+    }
+}
+
+fn by_origin(bms: &[Bookmark], origin: Origin) -> Vec<Bookmark> {
+    bms.iter()
+        .filter(|bm| bm.origin == origin)
+        .cloned()
+        .collect()
+}
+
+fn read_bookmarks(path: &Path, origin: Origin) -> Result<Vec<Bookmark>, String> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let store: Store =
+        toml::from_str(&raw).map_err(|e| format!("Unable to parse {}: {}", path.display(), e))?;
+    let mut bms = store.bookmark;
+    for bm in bms.iter_mut() {
+        bm.origin = origin;
+    }
+    Ok(bms)
+}
+
+/// Writes the bookmarks, leaving alone a file that doesn't parse (so that it
+/// can be fixed by hand rather than lost).
+fn write_bookmarks(path: &Path, bms: &[Bookmark]) -> Result<(), String> {
+    if path.exists() {
+        if let Err(msg) = read_bookmarks(path, Origin::User) {
+            return Err(format!("Not saving bookmarks: {}", msg));
+        }
+    }
+    std::fs::write(path, bundle(bms))
+        .map_err(|e| format!("Error writing {}: {}", path.display(), e))
+}
+
+/// Serializes the bookmarks into a portable bundle, without their origin.
+pub fn bundle(bms: &[Bookmark]) -> String {
+    let store = Store {
+        bookmark: bms.to_vec(),
+    };
+    toml::to_string(&store).expect("Unable to serialize bookmarks")
+}
+
+pub fn unbundle(path: &str) -> Result<Vec<Bookmark>, String> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read bookmarks from {}: {}", path, e))?;
+    let store: Store = toml::from_str(&raw)
+        .map_err(|e| format!("Unable to parse bookmarks from {}: {}", path, e))?;
+    let mut bms = store.bookmark;
+    for bm in bms.iter_mut().filter(|bm| bm.created == 0) {
+        bm.created = now();
+    }
+    Ok(bms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_literally() {
+        assert!(glob_match("data.json", "data.json"));
+        assert!(!glob_match("data.json", "data.jsonl"));
+        assert!(!glob_match("data.json", "data"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("*.json", "data.json"));
+        assert!(!glob_match("*.json", "data.yaml"));
+        assert!(glob_match("data?.json", "data1.json"));
+        assert!(!glob_match("data?.json", "data.json"));
+        assert!(glob_match("logs/*/*.json", "logs/2021/app.json"));
+        assert!(glob_match("**", "a/b"));
+    }
+
+    #[test]
+    fn glob_backtracks() {
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(glob_match("*.json", "a.json.json"));
+        assert!(!glob_match("*a*b", "xaxxa"));
+        assert!(glob_match("a*", "aaa"));
+    }
+
+    #[test]
+    fn glob_matches_multibyte() {
+        assert!(glob_match("d?ta.json", "dáta.json"));
+        assert!(glob_match("*é", "café"));
+    }
+}
@@ -0,0 +1,106 @@
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Copies the text to the system clipboard.
+///
+/// Emits an OSC 52 sequence, which the terminal (even over SSH) may honour,
+/// and also hands the text to the first external helper that's available.
+pub fn copy(text: &str) -> Result<String, String> {
+    osc52(text).map_err(|e| format!("Unable to copy to clipboard: {}", e))?;
+    for helper in helpers() {
+        if pipe(&helper, text) {
+            return Ok(format!("Copied to clipboard with {}", helper[0]));
+        }
+    }
+    Ok(String::from("Copied to clipboard"))
+}
+
+fn osc52(text: &str) -> io::Result<()> {
+    let mut sequence = format!("\x1b]52;c;{}\x07", encode(text.as_bytes()));
+    if env::var("TMUX").is_ok() {
+        sequence = format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+    }
+    let mut stdout = io::stdout();
+    stdout.write_all(sequence.as_bytes())?;
+    stdout.flush()
+}
+
+/// Clipboard helpers worth trying in the current session, in order.
+fn helpers() -> Vec<Vec<&'static str>> {
+    let mut helpers = Vec::new();
+    if env::var("WAYLAND_DISPLAY").is_ok() {
+        helpers.push(vec!["wl-copy"]);
+    }
+    if env::var("DISPLAY").is_ok() {
+        helpers.push(vec!["xclip", "-selection", "clipboard"]);
+        helpers.push(vec!["xsel", "--clipboard", "--input"]);
+    }
+    if cfg!(target_os = "macos") {
+        helpers.push(vec!["pbcopy"]);
+    }
+    helpers
+}
+
+fn pipe(helper: &[&str], text: &str) -> bool {
+    let child = Command::new(helper[0])
+        .args(&helper[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        if stdin.write_all(text.as_bytes()).is_err() {
+            return false;
+        }
+    }
+    child.wait().is_ok_and(|status| status.success())
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(BASE64[(n >> (18 - i * 6)) & 63] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_with_padding() {
+        // Test vectors from RFC 4648.
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foob"), "Zm9vYg==");
+        assert_eq!(encode(b"fooba"), "Zm9vYmE=");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn encodes_high_bytes() {
+        assert_eq!(encode(&[0xff, 0xfe, 0xfd]), "//79");
+        assert_eq!(encode("é".as_bytes()), "w6k=");
+    }
+}
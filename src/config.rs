@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE: &str = "config.toml";
+const PROJECT_DIR: &str = ".ijqrs";
+const HISTORY_FILE: &str = "history.toml";
+const HISTORY_SIZE: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub filter: String,
+    pub mouse: bool,
+    pub watch: bool,
+    pub jq: JqConfig,
+    pub theme: ThemeConfig,
+    pub layout: LayoutConfig,
+    pub output: OutputConfig,
+    pub keys: KeysConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            filter: String::from(".|keys"),
+            mouse: true,
+            watch: false,
+            jq: JqConfig::default(),
+            theme: ThemeConfig::default(),
+            layout: LayoutConfig::default(),
+            output: OutputConfig::default(),
+            keys: KeysConfig::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JqConfig {
+    pub path: String,
+    pub options: Vec<String>,
+    /// Seconds to wait for jq before giving up, 0 to wait forever.
+    pub timeout: u64,
+}
+
+impl Default for JqConfig {
+    fn default() -> Self {
+        JqConfig {
+            path: String::from("jq"),
+            options: Vec::new(),
+            timeout: 10,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// One of `dark`, `light`, `solarized` or `high-contrast`.
+    pub preset: String,
+    /// One of `auto`, `truecolor`, `256` or `16`.
+    pub colors: String,
+    pub background: Option<String>,
+    pub foreground: Option<String>,
+    pub active: Option<String>,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        ThemeConfig {
+            preset: String::from("dark"),
+            colors: String::from("auto"),
+            background: None,
+            foreground: None,
+            active: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Source panel width, in percent of the screen.
+    pub split: u16,
+    pub command_height: u16,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig {
+            split: 50,
+            command_height: 3,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub command: String,
+    pub result: String,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            command: String::from("ijqrs.cmd"),
+            result: String::from("ijqrs.out"),
+        }
+    }
+}
+
+/// Key bindings by action name, replacing the defaults for the listed actions.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysConfig {
+    pub global: BTreeMap<String, Vec<String>>,
+    pub content: BTreeMap<String, Vec<String>>,
+    pub bookmarks: BTreeMap<String, Vec<String>>,
+}
+
+/// Loads `config.toml` from the user config directory, defaults if there is none.
+pub fn load() -> Result<Config, String> {
+    let path = get_config_path().join(CONFIG_FILE);
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let raw = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let config: Config =
+        toml::from_str(&raw).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    if config.layout.split > 100 {
+        return Err(format!(
+            "Invalid {}: layout.split must be a percentage",
+            path.display()
+        ));
+    }
+    if config.layout.command_height < 3 {
+        return Err(format!(
+            "Invalid {}: layout.command_height must be at least 3",
+            path.display()
+        ));
+    }
+    Ok(config)
+}
+
+pub fn get_config_path() -> PathBuf {
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("HOMEPATH"))
+        .expect("Unable to resolve user home directory");
+    let path = Path::new(&home).join(".config").join("ijqrs");
+
+    if !path.exists() {
+        let dir = path
+            .to_str()
+            .expect("Unable to resolve the user config directory");
+        std::fs::create_dir_all(dir).expect("Unable to create the missing config directory");
+    }
+
+    path
+}
+
+/// Looks for the closest `.ijqrs` directory, walking up from cwd.
+pub fn get_project_path() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    for dir in cwd.ancestors() {
+        let path = dir.join(PROJECT_DIR);
+        if path.is_dir() {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Project settings, from `.ijqrs/config.toml`: only the jq options so far.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProjectConfig {
+    jq: ProjectJqConfig,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProjectJqConfig {
+    options: Vec<String>,
+}
+
+/// Default jq options of the project, added to the configured ones.
+pub fn get_jq_options() -> Result<Vec<String>, String> {
+    let path = match get_project_path() {
+        Some(dir) => dir.join(CONFIG_FILE),
+        None => return Ok(Vec::new()),
+    };
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let config: ProjectConfig =
+        toml::from_str(&raw).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    Ok(config.jq.options)
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct History {
+    #[serde(default)]
+    entries: Vec<String>,
+}
+
+/// History is kept with the project if there is one, globally otherwise.
+pub fn get_history_file_path() -> PathBuf {
+    get_project_path()
+        .unwrap_or_else(get_config_path)
+        .join(HISTORY_FILE)
+}
+
+pub fn load_history(path: &Path) -> Vec<String> {
+    let raw = std::fs::read_to_string(path).unwrap_or_default();
+    let history: History = toml::from_str(&raw).unwrap_or_default();
+    history.entries
+}
+
+pub fn save_history(path: &Path, entries: &[String]) -> Result<(), String> {
+    let skip = entries.len().saturating_sub(HISTORY_SIZE);
+    let history = History {
+        entries: entries[skip..].to_vec(),
+    };
+    let raw = toml::to_string(&history).map_err(|e| format!("Unable to save history: {}", e))?;
+    std::fs::write(path, raw).map_err(|e| format!("Error writing {}: {}", path.display(), e))
+}
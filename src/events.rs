@@ -1,36 +1,43 @@
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::terminal;
+use std::time::Duration;
+use tui::layout::Rect;
 
 use super::app;
+use super::keymap::{Action, Context, Resolved};
 use super::ui;
+use super::ui::Pane;
 
-fn handle_key_event(key: KeyEvent, state: &mut app::State) -> app::Signal {
-    match key {
-        KeyEvent {
-            code: KeyCode::Char('q'),
-            modifiers: KeyModifiers::CONTROL,
-        } => app::Signal::Quit,
-        KeyEvent {
-            code: KeyCode::Char('w'),
-            modifiers: KeyModifiers::CONTROL,
-        } => {
+const WHEEL_LINES: usize = 3;
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+fn handle_action(action: Action, state: &mut app::State) -> app::Signal {
+    match action {
+        Action::Quit => app::Signal::Quit,
+        Action::SwitchPanel => {
             let active = match state.get_active().get_type() {
                 ui::Panel::Command => ui::Panel::Output,
                 ui::Panel::Output => ui::Panel::Source,
                 ui::Panel::Source => ui::Panel::Command,
                 ui::Panel::Help => ui::Panel::Help,
                 ui::Panel::Bookmarks => ui::Panel::Bookmarks,
+                ui::Panel::Browser => ui::Panel::Browser,
+                ui::Panel::Popup => ui::Panel::Popup,
             };
             state.set_active(active);
             app::Signal::Nop
         }
-        KeyEvent {
-            code: KeyCode::Char('a'),
-            modifiers: KeyModifiers::CONTROL,
-        } => app::Signal::AddBookmark,
-        KeyEvent {
-            code: KeyCode::Char('s'),
-            modifiers: KeyModifiers::CONTROL,
-        } => {
+        Action::AddBookmark => app::Signal::AddBookmark,
+        Action::Edit => app::Signal::Edit,
+        Action::DrillDown => {
+            state.drill_down();
+            app::Signal::Nop
+        }
+        Action::DrillUp => {
+            state.drill_up();
+            app::Signal::Nop
+        }
+        Action::ToggleBookmarks => {
             let new_mode = match state.mode() {
                 app::Mode::Bookmarks => app::Mode::Shell,
                 _ => app::Mode::Bookmarks,
@@ -38,33 +45,199 @@ fn handle_key_event(key: KeyEvent, state: &mut app::State) -> app::Signal {
             state.set_mode(new_mode);
             app::Signal::Nop
         }
-        KeyEvent { code, modifiers } => match code {
-            KeyCode::Esc => {
-                state.get_mut_active().handle_event(code, modifiers);
-                state.switch_mode();
-                state.set_active(ui::Panel::Command);
-                app::Signal::Nop
+        Action::Help => {
+            state.set_mode(app::Mode::Help);
+            app::Signal::Nop
+        }
+        Action::ScrollDown => {
+            state.get_mut_active().scroll_down();
+            app::Signal::Nop
+        }
+        Action::ScrollUp => {
+            state.get_mut_active().scroll_up();
+            app::Signal::Nop
+        }
+        _ => state.get_mut_active().handle_action(action),
+    }
+}
+
+/// Scrolls the popup with the content panels navigation keys, any other key
+/// dismisses it. Characters are left to text inputs, which keep on being typed
+/// in. Returns whether the key is used up.
+fn handle_popup_key(key: KeyEvent, state: &mut app::State) -> bool {
+    let text_input = state.get_active().accepts_text();
+    let popup = match state.popup.as_mut() {
+        Some((_, popup)) => popup,
+        None => return false,
+    };
+    let typed = text_input && matches!(key.code, KeyCode::Char(_));
+    if !typed {
+        match state.keymap.resolve(Context::Content, key, false) {
+            Resolved::Pending => return true,
+            Resolved::Action(action) => match action {
+                Action::ScrollUp => {
+                    popup.scroll_up();
+                    return true;
+                }
+                Action::ScrollDown => {
+                    popup.scroll_down();
+                    return true;
+                }
+                Action::PageUp
+                | Action::PageDown
+                | Action::HalfPageUp
+                | Action::HalfPageDown
+                | Action::ScrollTop
+                | Action::ScrollBottom
+                | Action::ScrollLeft
+                | Action::ScrollRight
+                | Action::ToggleWrap => {
+                    popup.handle_action(action);
+                    return true;
+                }
+                _ => (),
+            },
+            Resolved::Unbound => (),
+        }
+    }
+    state.popup = None;
+    // Typing (or completing) carries on in text inputs, say after listing completions.
+    let typing = matches!(
+        key.code,
+        KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Tab
+    );
+    !(typing && text_input)
+}
+
+fn handle_key_event(key: KeyEvent, state: &mut app::State) -> app::Signal {
+    if handle_popup_key(key, state) {
+        return app::Signal::Nop;
+    }
+    let context = match (state.mode(), state.get_active().get_type()) {
+        (app::Mode::Bookmarks, _) => Context::Bookmarks,
+        (_, ui::Panel::Source) | (_, ui::Panel::Output) => Context::Content,
+        _ => Context::Global,
+    };
+    let captured = state.get_active().captures_key(key.code);
+    if !captured {
+        let text_input = state.get_active().accepts_text();
+        match state.keymap.resolve(context, key, text_input) {
+            Resolved::Action(action) => return handle_action(action, state),
+            Resolved::Pending => return app::Signal::Nop,
+            Resolved::Unbound => (),
+        }
+    }
+
+    let KeyEvent { code, modifiers } = key;
+    match code {
+        KeyCode::Esc => {
+            state.get_mut_active().handle_event(code, modifiers);
+            if captured {
+                return app::Signal::Nop;
+            }
+            state.switch_mode();
+            state.set_active(ui::Panel::Command);
+            app::Signal::Nop
+        }
+        _ => state.get_mut_active().handle_event(code, modifiers),
+    }
+}
+
+/// Position of the event relative to the area inner (borderless) part.
+fn inner_position(area: Rect, event: &MouseEvent) -> Option<(u16, u16)> {
+    let inside = event.column > area.x
+        && event.column < area.x + area.width.saturating_sub(1)
+        && event.row > area.y
+        && event.row < area.y + area.height.saturating_sub(1);
+    match inside {
+        true => Some((event.column - area.x - 1, event.row - area.y - 1)),
+        false => None,
+    }
+}
+
+fn handle_mouse_event(event: MouseEvent, state: &mut app::State) -> app::Signal {
+    let (width, height) = match terminal::size() {
+        Ok(size) => size,
+        Err(_) => return app::Signal::Nop,
+    };
+    let areas = ui::get_areas(Rect::new(0, 0, width, height), state);
+
+    if let Some((_, popup)) = state.popup.as_mut() {
+        match event.kind {
+            MouseEventKind::ScrollUp => (0..WHEEL_LINES).for_each(|_| popup.scroll_up()),
+            MouseEventKind::ScrollDown => (0..WHEEL_LINES).for_each(|_| popup.scroll_down()),
+            MouseEventKind::Down(_) => state.popup = None,
+            _ => (),
+        }
+        return app::Signal::Nop;
+    }
+
+    if let app::Mode::Bookmarks = state.mode() {
+        if let (MouseEventKind::Down(MouseButton::Left), Some((column, row))) =
+            (event.kind, inner_position(areas.bookmarks, &event))
+        {
+            state
+                .bookmarks
+                .click(column, row, areas.bookmarks.width - 2);
+        }
+        return app::Signal::Nop;
+    }
+    if let app::Mode::Browser = state.mode() {
+        match (event.kind, inner_position(areas.bookmarks, &event)) {
+            (MouseEventKind::Down(MouseButton::Left), Some((column, row))) => {
+                state.browser.click(column, row, areas.bookmarks.width - 2);
             }
-            KeyCode::Char('?') => {
-                state.set_mode(app::Mode::Help);
-                app::Signal::Nop
+            (MouseEventKind::ScrollUp, _) => state.browser.scroll_up(),
+            (MouseEventKind::ScrollDown, _) => state.browser.scroll_down(),
+            _ => (),
+        }
+        return app::Signal::Nop;
+    }
+
+    let panels = [
+        (ui::Panel::Source, areas.source),
+        (ui::Panel::Output, areas.output),
+        (ui::Panel::Command, areas.command),
+    ];
+    for (panel, area) in panels {
+        let (column, row) = match inner_position(area, &event) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        let content = match panel {
+            ui::Panel::Source => Some(&mut state.source),
+            ui::Panel::Output => Some(&mut state.output),
+            _ => None,
+        };
+        match (event.kind, content) {
+            (MouseEventKind::ScrollUp, Some(content)) => {
+                (0..WHEEL_LINES).for_each(|_| content.scroll_up());
             }
-            KeyCode::Down => {
-                state.get_mut_active().scroll_down();
-                app::Signal::Nop
+            (MouseEventKind::ScrollDown, Some(content)) => {
+                (0..WHEEL_LINES).for_each(|_| content.scroll_down());
             }
-            KeyCode::Up => {
-                state.get_mut_active().scroll_up();
-                app::Signal::Nop
+            (MouseEventKind::Down(MouseButton::Left), _) => {
+                state.set_active(panel);
+                state.get_mut_active().click(column, row, area.width - 2);
             }
-            _ => state.get_mut_active().handle_event(code, modifiers),
-        },
+            _ => (),
+        }
+        break;
     }
+    app::Signal::Nop
 }
 
 pub fn handler(state: &mut app::State) -> app::Signal {
-    if let Ok(Event::Key(key)) = event::read() {
-        return handle_key_event(key, state);
+    if state.watch && !event::poll(WATCH_INTERVAL).unwrap_or(false) {
+        return match state.source_changed() {
+            true => app::Signal::Reload,
+            false => app::Signal::Nop,
+        };
+    }
+    match event::read() {
+        Ok(Event::Key(key)) => handle_key_event(key, state),
+        Ok(Event::Mouse(mouse)) => handle_mouse_event(mouse, state),
+        Ok(Event::Resize(_, _)) => app::Signal::Resize,
+        _ => app::Signal::Nop,
     }
-    app::Signal::Nop
 }
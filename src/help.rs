@@ -1,50 +1,87 @@
 use std::collections::HashMap;
 
+use super::keymap::{Context, Keymap};
+
 const USAGE: &str = "Usage";
 const SHORTCUTS: &str = "Shortcuts";
 const BOOKMARKS: &str = "Bookmarks";
 const COMMANDS: &str = "Commands";
 
 // TODO: preserve ordering somehow
-pub fn get_help() -> HashMap<String, Vec<String>> {
+pub fn get_help(keymap: &Keymap) -> HashMap<String, Vec<String>> {
+    let mut shortcuts = vec!["\t - Esc: Switch mode".to_string()];
+    shortcuts.extend(describe(keymap, Context::Global));
+    shortcuts.extend(describe(keymap, Context::Content));
+    shortcuts.push("\t - Enter: Apply search pattern (in content panels search mode)".to_string());
+    shortcuts
+        .push("\t - Up/Down: Recall search history (in content panels search mode)".to_string());
+    shortcuts.push(
+        "\t - Search for key:PATTERN, value:PATTERN or type:TYPE to list matching JSON paths"
+            .to_string(),
+    );
+
+    let mut bookmarks = vec![
+        "\t - :ba [NAME] [#TAG]... [@FILE_PATTERN] [-- DESCRIPTION]: Add named bookmark"
+            .to_string(),
+        "\t - Enter: Use bookmark".to_string(),
+        "\t - Type to fuzzy filter bookmarks".to_string(),
+    ];
+    bookmarks.extend(describe(keymap, Context::Bookmarks));
+
     HashMap::from([
         (
             USAGE.to_string(),
             vec![
                 "ijqrd [FILE_NAME]".to_string(),
                 "If file name is ommited, will read from STDIN".to_string(),
+                "ijqrs --run BOOKMARK_NAME|FILTER [--format json|compact|raw] [FILE_NAME]"
+                    .to_string(),
+                "Runs the bookmark, or the filter, without the TUI and prints the result"
+                    .to_string(),
+                "Exits with the jq exit code, 2 when unable to run jq".to_string(),
             ],
         ),
-        (
-            SHORTCUTS.to_string(),
-            vec![
-                "\t - Ctrl+q: Quit".to_string(),
-                "\t - Ctrl+w: Switch panel".to_string(),
-                "\t - Esc: Switch mode".to_string(),
-                "\t - /: Start pattern search (in content panels)".to_string(),
-                "\t - Enter: Apply search pattern (in content panels search mode)".to_string(),
-                "\t - n: Next match (in content panels search mode)".to_string(),
-                "\t - N: Previous match (in content panels search mode)".to_string(),
-                "\t - Ctrl+l: Clear search (in content panels search mode)".to_string(),
-                "\t - ?: Show help".to_string(),
-            ],
-        ),
-        (
-            BOOKMARKS.to_string(),
-            vec![
-                "\t - Ctrl+s: Open/Close bookmarks interface".to_string(),
-                "\t - Ctrl+a: Add bookmark".to_string(),
-                "\t - Ctrl+d|Del: Delete bookmark".to_string(),
-                "\t - Enter: Use bookmark".to_string(),
-            ],
-        ),
+        (SHORTCUTS.to_string(), shortcuts),
+        (BOOKMARKS.to_string(), bookmarks),
         (
             COMMANDS.to_string(),
             vec![
-                "\t - :wc [FILE_NAME]: write the jq command string to file".to_string(),
-                "\t - :w [FILE_NAME]: write the command output to file".to_string(),
+                "\t - :wc[!] [FILE_NAME]: write the jq command string to file".to_string(),
+                "\t - :w[!] [FILE_NAME]: write the command output to file, ! to overwrite"
+                    .to_string(),
+                "\t - :w >>FILE_NAME: append the command output to file".to_string(),
+                "\t - :ws[!] [FILE_NAME]: write the filter as a runnable script (or .jq module)"
+                    .to_string(),
                 "\t - :r [FILE_NAME]: (re)-run the jq command string".to_string(),
+                "\t - :e, :reload: reload the file and re-run the jq command string".to_string(),
+                "\t - :e FILE_NAME: open another file (Tab completes), a directory to browse it"
+                    .to_string(),
+                "\t - File browser: Enter/Right to open, Backspace/Left for parent directory"
+                    .to_string(),
+                "\t - :watch [on|off]: reload the file whenever it changes".to_string(),
+                "\t - :! SHELL_COMMAND: pipe the command output to shell command, show what it prints (scroll it, any other key closes)"
+                    .to_string(),
+                "\t - :| SHELL_COMMAND: pipe the command output to shell command, replace it with what it prints"
+                    .to_string(),
+                "\t - :yc: copy the jq command string to clipboard".to_string(),
+                "\t - :y: copy the command output to clipboard".to_string(),
+                "\t - :wsel[!] [FILE_NAME]: write the content panel selection to file".to_string(),
+                "\t - :rsel: run the jq command string on the content panel selection".to_string(),
+                "\t - Drilled down, :wc, :yc, :ws and :bookmarks share use the filters composed into one"
+                    .to_string(),
+                "\t - :LINE_NUMBER: jump to line in the last focused content panel".to_string(),
+                "\t - :bookmarks[!] export [FILE_NAME]: write all bookmarks to a bundle, ! to overwrite, >> to append".to_string(),
+                "\t - :bookmarks import FILE_NAME: merge bookmarks from a bundle".to_string(),
+                "\t - :bookmarks[!] share [FILE_NAME]: write the filter as a jq one-liner, ! to overwrite, >> to append".to_string(),
             ],
         ),
     ])
 }
+
+fn describe(keymap: &Keymap, context: Context) -> Vec<String> {
+    keymap
+        .describe(context)
+        .into_iter()
+        .map(|(keys, description)| format!("\t - {}: {}", keys, description))
+        .collect()
+}
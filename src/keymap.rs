@@ -0,0 +1,819 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+
+use super::config::KeysConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Context {
+    Global,
+    Content,
+    Bookmarks,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    SwitchPanel,
+    ToggleBookmarks,
+    AddBookmark,
+    Help,
+    Edit,
+    DrillDown,
+    DrillUp,
+    ScrollUp,
+    ScrollDown,
+    Search,
+    SearchNext,
+    SearchPrev,
+    ClearSearch,
+    ToggleRegex,
+    ToggleSmartCase,
+    ToggleWholeWord,
+    UseHit,
+    Yank,
+    SelectLines,
+    SelectBlock,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    ScrollTop,
+    ScrollBottom,
+    ScrollLeft,
+    ScrollRight,
+    ToggleWrap,
+    DeleteBookmark,
+    EditBookmark,
+    RenameBookmark,
+    PinBookmark,
+    MoveBookmarkUp,
+    MoveBookmarkDown,
+    UpdateBookmark,
+    ToggleBookmarkOrigin,
+}
+
+struct ActionInfo {
+    action: Action,
+    context: Context,
+    name: &'static str,
+    description: &'static str,
+    keys: &'static [&'static str],
+}
+
+/// All the bindable actions, with their default keys, in help order.
+const ACTIONS: &[ActionInfo] = &[
+    ActionInfo {
+        action: Action::Quit,
+        context: Context::Global,
+        name: "quit",
+        description: "Quit",
+        keys: &["ctrl+q"],
+    },
+    ActionInfo {
+        action: Action::SwitchPanel,
+        context: Context::Global,
+        name: "switch_panel",
+        description: "Switch panel",
+        keys: &["ctrl+w"],
+    },
+    ActionInfo {
+        action: Action::Help,
+        context: Context::Global,
+        name: "help",
+        description: "Show help",
+        keys: &["?", "f1"],
+    },
+    ActionInfo {
+        action: Action::ScrollUp,
+        context: Context::Global,
+        name: "scroll_up",
+        description: "Scroll up (previous command in command panel)",
+        keys: &["up"],
+    },
+    ActionInfo {
+        action: Action::ScrollDown,
+        context: Context::Global,
+        name: "scroll_down",
+        description: "Scroll down (next command in command panel)",
+        keys: &["down"],
+    },
+    ActionInfo {
+        action: Action::Search,
+        context: Context::Content,
+        name: "search",
+        description: "Start pattern search (in content panels)",
+        keys: &["/"],
+    },
+    ActionInfo {
+        action: Action::SearchNext,
+        context: Context::Content,
+        name: "search_next",
+        description: "Next match (in content panels search mode)",
+        keys: &["n"],
+    },
+    ActionInfo {
+        action: Action::SearchPrev,
+        context: Context::Content,
+        name: "search_prev",
+        description: "Previous match (in content panels search mode)",
+        keys: &["N"],
+    },
+    ActionInfo {
+        action: Action::ClearSearch,
+        context: Context::Content,
+        name: "clear_search",
+        description: "Clear search (in content panels search mode)",
+        keys: &["ctrl+l"],
+    },
+    ActionInfo {
+        action: Action::ToggleRegex,
+        context: Context::Content,
+        name: "toggle_regex",
+        description: "Toggle regex search (in content panels)",
+        keys: &["alt+r"],
+    },
+    ActionInfo {
+        action: Action::ToggleSmartCase,
+        context: Context::Content,
+        name: "toggle_smart_case",
+        description: "Toggle smart case search, case sensitive when off (in content panels)",
+        keys: &["alt+c"],
+    },
+    ActionInfo {
+        action: Action::ToggleWholeWord,
+        context: Context::Content,
+        name: "toggle_whole_word",
+        description: "Toggle whole word search (in content panels)",
+        keys: &["alt+w"],
+    },
+    ActionInfo {
+        action: Action::UseHit,
+        context: Context::Content,
+        name: "use_hit",
+        description: "Use the structural search hit path as the filter (in content panels)",
+        keys: &["f"],
+    },
+    ActionInfo {
+        action: Action::Yank,
+        context: Context::Content,
+        name: "yank",
+        description: "Copy the selection, the search hit path or the panel to clipboard",
+        keys: &["y"],
+    },
+    ActionInfo {
+        action: Action::SelectLines,
+        context: Context::Content,
+        name: "select_lines",
+        description: "Start or end line selection (in content panels)",
+        keys: &["V"],
+    },
+    ActionInfo {
+        action: Action::SelectBlock,
+        context: Context::Content,
+        name: "select_block",
+        description: "Start or end block selection (in content panels)",
+        keys: &["ctrl+v"],
+    },
+    ActionInfo {
+        action: Action::PageUp,
+        context: Context::Content,
+        name: "page_up",
+        description: "Scroll up a page (in content panels)",
+        keys: &["pageup"],
+    },
+    ActionInfo {
+        action: Action::PageDown,
+        context: Context::Content,
+        name: "page_down",
+        description: "Scroll down a page (in content panels)",
+        keys: &["pagedown"],
+    },
+    ActionInfo {
+        action: Action::HalfPageUp,
+        context: Context::Content,
+        name: "half_page_up",
+        description: "Scroll up half a page (in content panels)",
+        keys: &["ctrl+u"],
+    },
+    ActionInfo {
+        action: Action::HalfPageDown,
+        context: Context::Content,
+        name: "half_page_down",
+        description: "Scroll down half a page (in content panels)",
+        keys: &["ctrl+d"],
+    },
+    ActionInfo {
+        action: Action::ScrollTop,
+        context: Context::Content,
+        name: "scroll_top",
+        description: "Go to the first line (in content panels)",
+        keys: &["g", "home"],
+    },
+    ActionInfo {
+        action: Action::ScrollBottom,
+        context: Context::Content,
+        name: "scroll_bottom",
+        description: "Go to the last line (in content panels)",
+        keys: &["G", "end"],
+    },
+    ActionInfo {
+        action: Action::ScrollLeft,
+        context: Context::Content,
+        name: "scroll_left",
+        description: "Scroll left (with wrapping off) (in content panels)",
+        keys: &["left", "h"],
+    },
+    ActionInfo {
+        action: Action::ScrollRight,
+        context: Context::Content,
+        name: "scroll_right",
+        description: "Scroll right (with wrapping off) (in content panels)",
+        keys: &["right", "l"],
+    },
+    ActionInfo {
+        action: Action::ToggleWrap,
+        context: Context::Content,
+        name: "toggle_wrap",
+        description: "Toggle line wrapping (in content panels)",
+        keys: &["w"],
+    },
+    ActionInfo {
+        action: Action::Edit,
+        context: Context::Global,
+        name: "edit",
+        description: "Open the filter, or the focused content panel, in $EDITOR",
+        keys: &["ctrl+o"],
+    },
+    ActionInfo {
+        action: Action::DrillDown,
+        context: Context::Global,
+        name: "drill_down",
+        description: "Use the result as the new source",
+        keys: &["ctrl+n"],
+    },
+    ActionInfo {
+        action: Action::DrillUp,
+        context: Context::Global,
+        name: "drill_up",
+        description: "Go back to the source and filter drilled down from",
+        keys: &["ctrl+b"],
+    },
+    ActionInfo {
+        action: Action::ToggleBookmarks,
+        context: Context::Global,
+        name: "toggle_bookmarks",
+        description: "Open/Close bookmarks interface",
+        keys: &["ctrl+s"],
+    },
+    ActionInfo {
+        action: Action::AddBookmark,
+        context: Context::Global,
+        name: "add_bookmark",
+        description: "Add bookmark",
+        keys: &["ctrl+a"],
+    },
+    ActionInfo {
+        action: Action::DeleteBookmark,
+        context: Context::Bookmarks,
+        name: "delete_bookmark",
+        description: "Delete bookmark",
+        keys: &["ctrl+d", "delete"],
+    },
+    ActionInfo {
+        action: Action::EditBookmark,
+        context: Context::Bookmarks,
+        name: "edit_bookmark",
+        description: "Edit bookmark filter, Enter to save",
+        keys: &["ctrl+e"],
+    },
+    ActionInfo {
+        action: Action::RenameBookmark,
+        context: Context::Bookmarks,
+        name: "rename_bookmark",
+        description: "Edit bookmark name, Enter to save",
+        keys: &["ctrl+r"],
+    },
+    ActionInfo {
+        action: Action::MoveBookmarkUp,
+        context: Context::Bookmarks,
+        name: "move_bookmark_up",
+        description: "Move bookmark up",
+        keys: &["alt+up"],
+    },
+    ActionInfo {
+        action: Action::MoveBookmarkDown,
+        context: Context::Bookmarks,
+        name: "move_bookmark_down",
+        description: "Move bookmark down",
+        keys: &["alt+down"],
+    },
+    ActionInfo {
+        action: Action::PinBookmark,
+        context: Context::Bookmarks,
+        name: "pin_bookmark",
+        description: "Pin/Unpin bookmark to the top",
+        keys: &["ctrl+f"],
+    },
+    ActionInfo {
+        action: Action::UpdateBookmark,
+        context: Context::Bookmarks,
+        name: "update_bookmark",
+        description: "Update bookmark from the current command",
+        keys: &["ctrl+u"],
+    },
+    ActionInfo {
+        action: Action::ToggleBookmarkOrigin,
+        context: Context::Bookmarks,
+        name: "toggle_bookmark_origin",
+        description: "Move bookmark between user and project (.ijqrs/) bookmarks",
+        keys: &["ctrl+p"],
+    },
+    ActionInfo {
+        action: Action::ClearSearch,
+        context: Context::Bookmarks,
+        name: "clear_search",
+        description: "Clear bookmarks filter",
+        keys: &["ctrl+l"],
+    },
+];
+
+struct Binding {
+    context: Context,
+    keys: Vec<KeyEvent>,
+    action: Action,
+    /// Whether the binding comes from the config, rather than the defaults.
+    custom: bool,
+}
+
+pub enum Resolved {
+    Action(Action),
+    Pending,
+    Unbound,
+}
+
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    pending: Vec<KeyEvent>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings = Vec::new();
+        for info in ACTIONS {
+            for keys in info.keys {
+                bindings.push(Binding {
+                    context: info.context,
+                    keys: parse_sequence(keys).expect("Invalid default key binding"),
+                    action: info.action,
+                    custom: false,
+                });
+            }
+        }
+        Keymap {
+            bindings,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Default keymap, with the actions listed in config rebound to the configured keys.
+    pub fn from_config(config: &KeysConfig) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+        let sections = [
+            (Context::Global, &config.global),
+            (Context::Content, &config.content),
+            (Context::Bookmarks, &config.bookmarks),
+        ];
+        for (context, section) in sections {
+            keymap.rebind(context, section)?;
+        }
+        keymap.validate()?;
+        Ok(keymap)
+    }
+
+    /// Reports the bindings that can never fire, in the panels where they're
+    /// active along with another one: the same keys bound to different actions,
+    /// or keys starting with the whole of another binding.
+    fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        for (idx, a) in self.bindings.iter().enumerate() {
+            for b in &self.bindings[idx + 1..] {
+                if !overlaps(a.context, b.context) {
+                    continue;
+                }
+                if a.keys == b.keys {
+                    if a.action != b.action {
+                        problems.push(format!(
+                            "{} is bound to both {} and {}",
+                            format_sequence(&a.keys),
+                            action_name(a.action),
+                            action_name(b.action)
+                        ));
+                    }
+                    continue;
+                }
+                let (short, long) = match a.keys.len() < b.keys.len() {
+                    true => (a, b),
+                    false => (b, a),
+                };
+                if long.keys.starts_with(&short.keys) {
+                    problems.push(format!(
+                        "{} ({}) is shadowed by {} ({})",
+                        format_sequence(&long.keys),
+                        action_name(long.action),
+                        format_sequence(&short.keys),
+                        action_name(short.action)
+                    ));
+                }
+            }
+        }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(format!("Conflicting key bindings: {}", problems.join("; "))),
+        }
+    }
+
+    fn rebind(
+        &mut self,
+        context: Context,
+        section: &BTreeMap<String, Vec<String>>,
+    ) -> Result<(), String> {
+        for (name, keys) in section {
+            let info = ACTIONS
+                .iter()
+                .find(|info| info.context == context && info.name == name)
+                .ok_or(format!("Unknown key binding action: {}", name))?;
+            self.bindings
+                .retain(|b| !(b.context == context && b.action == info.action));
+            for key in keys {
+                let keys = parse_sequence(key)?;
+                // Configured keys are taken over from the defaults of the section.
+                self.bindings
+                    .retain(|b| b.custom || b.context != context || b.keys != keys);
+                self.bindings.push(Binding {
+                    context,
+                    keys,
+                    action: info.action,
+                    custom: true,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Feeds the key into the pending sequence, looking for a binding in the
+    /// context first and globally afterwards. Plain characters are left alone
+    /// when the active panel takes text input.
+    pub fn resolve(&mut self, context: Context, key: KeyEvent, text_input: bool) -> Resolved {
+        let key = normalize(key);
+        if text_input && is_text(&key) && self.pending.is_empty() {
+            return Resolved::Unbound;
+        }
+        self.pending.push(key);
+        match self.lookup(context) {
+            Resolved::Unbound if self.pending.len() > 1 => {
+                self.pending.clear();
+                self.resolve(context, key, text_input)
+            }
+            Resolved::Pending => Resolved::Pending,
+            resolved => {
+                self.pending.clear();
+                resolved
+            }
+        }
+    }
+
+    fn lookup(&self, context: Context) -> Resolved {
+        let mut pending = false;
+        for ctx in [context, Context::Global] {
+            for binding in self.bindings.iter().filter(|b| b.context == ctx) {
+                if binding.keys == self.pending {
+                    return Resolved::Action(binding.action);
+                }
+                if binding.keys.starts_with(&self.pending) {
+                    pending = true;
+                }
+            }
+        }
+        match pending {
+            true => Resolved::Pending,
+            false => Resolved::Unbound,
+        }
+    }
+
+    /// Active bindings as `(keys, description)` help lines for the context.
+    pub fn describe(&self, context: Context) -> Vec<(String, String)> {
+        let mut lines = Vec::new();
+        for info in ACTIONS.iter().filter(|info| info.context == context) {
+            let keys: Vec<String> = self
+                .bindings
+                .iter()
+                .filter(|b| b.context == context && b.action == info.action)
+                .map(|b| format_sequence(&b.keys))
+                .collect();
+            if !keys.is_empty() {
+                lines.push((keys.join("|"), info.description.to_string()));
+            }
+        }
+        lines
+    }
+}
+
+/// Whether bindings of the contexts can be active at the same time.
+fn overlaps(a: Context, b: Context) -> bool {
+    a == b || a == Context::Global || b == Context::Global
+}
+
+fn action_name(action: Action) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|info| info.action == action)
+        .map(|info| info.name)
+        .unwrap_or("unknown")
+}
+
+fn normalize(key: KeyEvent) -> KeyEvent {
+    match key.code {
+        KeyCode::Char(_) => KeyEvent {
+            code: key.code,
+            modifiers: key.modifiers - KeyModifiers::SHIFT,
+        },
+        _ => key,
+    }
+}
+
+fn is_text(key: &KeyEvent) -> bool {
+    matches!(key.code, KeyCode::Char(_))
+        && !key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+}
+
+/// Parses space separated key chords, such as `ctrl+w` or `g g`.
+pub fn parse_sequence(raw: &str) -> Result<Vec<KeyEvent>, String> {
+    let keys: Result<Vec<KeyEvent>, String> = raw.split_whitespace().map(parse_chord).collect();
+    match keys {
+        Ok(keys) if keys.is_empty() => Err(String::from("Empty key binding")),
+        keys => keys,
+    }
+}
+
+fn parse_chord(raw: &str) -> Result<KeyEvent, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = raw.split('+').collect();
+    let key = match parts.pop() {
+        Some("") if raw.ends_with("++") || raw == "+" => "+",
+        Some(key) => key,
+        None => return Err(format!("Invalid key: {}", raw)),
+    };
+    for part in parts.iter().filter(|p| !p.is_empty()) {
+        modifiers |= match part.to_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" | "meta" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(format!("Invalid key modifier in: {}", raw)),
+        };
+    }
+    let code = match key.to_lowercase().as_str() {
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        "space" => KeyCode::Char(' '),
+        lower if lower.len() > 1 && lower.starts_with('f') => match lower[1..].parse::<u8>() {
+            Ok(num) if (1..=12).contains(&num) => KeyCode::F(num),
+            _ => return Err(format!("Invalid key: {}", raw)),
+        },
+        _ => {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                    KeyCode::Char(c.to_ascii_uppercase())
+                }
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(format!("Invalid key: {}", raw)),
+            }
+        }
+    };
+    Ok(normalize(KeyEvent { code, modifiers }))
+}
+
+fn format_sequence(keys: &[KeyEvent]) -> String {
+    let chords: Vec<String> = keys.iter().map(format_chord).collect();
+    chords.join(" ")
+}
+
+fn format_chord(key: &KeyEvent) -> String {
+    let mut out = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        out.push_str("Ctrl+");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        out.push_str("Alt+");
+    }
+    if key.modifiers.contains(KeyModifiers::SHIFT) {
+        out.push_str("Shift+");
+    }
+    let name = match key.code {
+        KeyCode::Char(' ') => String::from("Space"),
+        KeyCode::Char(c) => String::from(c),
+        KeyCode::F(num) => format!("F{}", num),
+        KeyCode::Delete => String::from("Del"),
+        KeyCode::PageUp => String::from("PgUp"),
+        KeyCode::PageDown => String::from("PgDn"),
+        code => format!("{:?}", code),
+    };
+    out.push_str(&name);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent { code, modifiers }
+    }
+
+    fn keys_config(section: &str, action: &str, keys: &[&str]) -> KeysConfig {
+        let mut config = KeysConfig::default();
+        let bindings = keys.iter().map(|k| k.to_string()).collect();
+        match section {
+            "global" => config.global.insert(action.to_string(), bindings),
+            "content" => config.content.insert(action.to_string(), bindings),
+            _ => config.bookmarks.insert(action.to_string(), bindings),
+        };
+        config
+    }
+
+    #[test]
+    fn parses_chords() {
+        let ctrl = KeyModifiers::CONTROL;
+        assert_eq!(parse_chord("ctrl+w"), Ok(key(KeyCode::Char('w'), ctrl)));
+        assert_eq!(parse_chord("Control+W"), Ok(key(KeyCode::Char('W'), ctrl)));
+        assert_eq!(
+            parse_chord("alt+up"),
+            Ok(key(KeyCode::Up, KeyModifiers::ALT))
+        );
+        assert_eq!(
+            parse_chord("f12"),
+            Ok(key(KeyCode::F(12), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_chord("space"),
+            Ok(key(KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_chord("shift+a"),
+            Ok(key(KeyCode::Char('A'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_chord("shift+tab"),
+            Ok(key(KeyCode::Tab, KeyModifiers::SHIFT))
+        );
+    }
+
+    #[test]
+    fn parses_plus_key() {
+        assert_eq!(
+            parse_chord("+"),
+            Ok(key(KeyCode::Char('+'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_chord("ctrl++"),
+            Ok(key(KeyCode::Char('+'), KeyModifiers::CONTROL))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_chords() {
+        assert!(parse_chord("f13").is_err());
+        assert!(parse_chord("hyper+x").is_err());
+        assert!(parse_chord("ctrl+ab").is_err());
+        assert!(parse_sequence("").is_err());
+        assert!(parse_sequence("   ").is_err());
+    }
+
+    #[test]
+    fn parses_sequences() {
+        assert_eq!(
+            parse_sequence("ctrl+g  w"),
+            Ok(vec![
+                key(KeyCode::Char('g'), KeyModifiers::CONTROL),
+                key(KeyCode::Char('w'), KeyModifiers::NONE),
+            ])
+        );
+    }
+
+    #[test]
+    fn default_bindings_are_valid() {
+        assert!(Keymap::default().validate().is_ok());
+    }
+
+    #[test]
+    fn resolves_in_context_then_globally() {
+        let mut keymap = Keymap::default();
+        let g = key(KeyCode::Char('g'), KeyModifiers::NONE);
+        let ctrl_w = key(KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert!(matches!(
+            keymap.resolve(Context::Content, g, false),
+            Resolved::Action(Action::ScrollTop)
+        ));
+        assert!(matches!(
+            keymap.resolve(Context::Content, ctrl_w, false),
+            Resolved::Action(Action::SwitchPanel)
+        ));
+        assert!(matches!(
+            keymap.resolve(Context::Global, g, false),
+            Resolved::Unbound
+        ));
+    }
+
+    #[test]
+    fn leaves_typed_characters_to_text_input() {
+        let mut keymap = Keymap::default();
+        let g = key(KeyCode::Char('g'), KeyModifiers::NONE);
+        assert!(matches!(
+            keymap.resolve(Context::Content, g, true),
+            Resolved::Unbound
+        ));
+    }
+
+    #[test]
+    fn resolves_sequences() {
+        let config = keys_config("global", "switch_panel", &["ctrl+g w"]);
+        let mut keymap = Keymap::from_config(&config).unwrap();
+        let ctrl_g = key(KeyCode::Char('g'), KeyModifiers::CONTROL);
+        let w = key(KeyCode::Char('w'), KeyModifiers::NONE);
+        assert!(matches!(
+            keymap.resolve(Context::Global, ctrl_g, true),
+            Resolved::Pending
+        ));
+        assert!(matches!(
+            keymap.resolve(Context::Global, w, true),
+            Resolved::Action(Action::SwitchPanel)
+        ));
+        // The default key is replaced.
+        let ctrl_w = key(KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert!(matches!(
+            keymap.resolve(Context::Global, ctrl_w, false),
+            Resolved::Unbound
+        ));
+    }
+
+    #[test]
+    fn restarts_broken_sequences_with_the_last_key() {
+        let config = keys_config("global", "switch_panel", &["ctrl+g w"]);
+        let mut keymap = Keymap::from_config(&config).unwrap();
+        let ctrl_g = key(KeyCode::Char('g'), KeyModifiers::CONTROL);
+        let ctrl_q = key(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        keymap.resolve(Context::Global, ctrl_g, false);
+        assert!(matches!(
+            keymap.resolve(Context::Global, ctrl_q, false),
+            Resolved::Action(Action::Quit)
+        ));
+    }
+
+    #[test]
+    fn configured_keys_take_over_from_defaults() {
+        let config = keys_config("global", "switch_panel", &["ctrl+q"]);
+        let mut keymap = Keymap::from_config(&config).unwrap();
+        let ctrl_q = key(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        assert!(matches!(
+            keymap.resolve(Context::Global, ctrl_q, false),
+            Resolved::Action(Action::SwitchPanel)
+        ));
+    }
+
+    #[test]
+    fn reports_conflicts_across_contexts() {
+        let config = keys_config("content", "scroll_top", &["ctrl+w"]);
+        let err = Keymap::from_config(&config).err().unwrap();
+        assert!(err.contains("bound to both"), "{}", err);
+    }
+
+    #[test]
+    fn reports_shadowed_sequences() {
+        let config = keys_config("global", "switch_panel", &["ctrl+q w"]);
+        let err = Keymap::from_config(&config).err().unwrap();
+        assert!(err.contains("is shadowed by"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unknown_actions() {
+        let config = keys_config("global", "no_such_action", &["ctrl+x"]);
+        assert!(Keymap::from_config(&config).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use std::io;
+use std::process;
 
 use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -8,8 +10,13 @@ use tui::{backend::CrosstermBackend, Terminal};
 
 pub mod actions;
 pub mod app;
+pub mod batch;
+pub mod bookmarks;
+pub mod clipboard;
+pub mod config;
 pub mod events;
 pub mod help;
+pub mod keymap;
 pub mod opts;
 pub mod ui;
 
@@ -23,6 +30,13 @@ fn main() {
         opts::Flags::Version => {
             show_version();
         }
+        opts::Flags::Run(batch) => {
+            process::exit(batch::run(&batch));
+        }
+        opts::Flags::Invalid(msg) => {
+            eprintln!("ijqrs: {}", msg);
+            process::exit(batch::EXIT_ERROR);
+        }
         opts::Flags::Stdin => {
             let mut app: app::State = app::State::from_stdin();
             run(&mut app);
@@ -35,7 +49,13 @@ fn main() {
 }
 
 fn show_help() {
-    let hlp = help::get_help();
+    let keymap = config::load()
+        .and_then(|config| keymap::Keymap::from_config(&config.keys))
+        .unwrap_or_else(|msg| {
+            eprintln!("ijqrs: {}", msg);
+            keymap::Keymap::default()
+        });
+    let hlp = help::get_help(&keymap);
     println!();
     for title in hlp.keys() {
         println!("--- {}: ---", title);
@@ -50,38 +70,91 @@ fn show_version() {
     println!("{}", VERSION);
 }
 
-fn run(app: &mut app::State) {
+type Term = Terminal<CrosstermBackend<io::Stdout>>;
+
+/// Takes over the terminal for the TUI.
+fn resume(terminal: &mut Term, mouse: bool) {
     execute!(io::stdout(), EnterAlternateScreen).expect("Unable to enter alternate screen");
+    enable_raw_mode().expect("Could not enable raw mode");
+    if mouse {
+        execute!(io::stdout(), EnableMouseCapture).expect("Unable to enable mouse capture");
+    }
+    terminal.clear().expect("Unable to clear the terminal");
+}
+
+/// Gives the terminal back, as it was before the TUI.
+fn suspend(terminal: &mut Term, mouse: bool) {
+    terminal.clear().expect("Unable to clear the terminal");
+    terminal
+        .show_cursor()
+        .expect("Unable to re-show the cursor");
+    if mouse {
+        execute!(io::stdout(), DisableMouseCapture).expect("Unable to disable mouse capture");
+    }
+    disable_raw_mode().expect("Could not disable raw mode");
+    execute!(io::stdout(), LeaveAlternateScreen).expect("Unable to leave alternate screen");
+}
+
+fn run(app: &mut app::State) {
     let stdout = io::stdout();
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend).expect("Unable to bootstrap terminal");
-
-    enable_raw_mode().expect("Could not enable raw mode");
+    resume(&mut terminal, app.config.mouse);
     loop {
+        app.check_bookmarks();
         terminal
             .draw(|frame| ui::draw(frame, app))
             .expect("Could not draw UI");
         let sig = events::handler(app);
         match sig {
             app::Signal::Quit => {
-                terminal.clear().expect("Unable to clear the terminal");
-                terminal
-                    .show_cursor()
-                    .expect("Unable to re-show the cursor");
-                disable_raw_mode().expect("Could not disable raw mode");
-                execute!(io::stdout(), LeaveAlternateScreen)
-                    .expect("Unable to leave alternate screen");
+                app.close();
+                suspend(&mut terminal, app.config.mouse);
                 return;
             }
+            app::Signal::Reload => {
+                app.reload_source();
+            }
+            app::Signal::Open(fname) => {
+                app.open_file(&fname);
+            }
+            app::Signal::Complete => {
+                app.complete();
+            }
+            app::Signal::Edit => {
+                let (text, filter) = app.get_editable();
+                let extension = if filter { "jq" } else { "json" };
+                suspend(&mut terminal, app.config.mouse);
+                let edited = actions::edit(&text, extension);
+                resume(&mut terminal, app.config.mouse);
+                app.apply_edit(edited, filter);
+            }
             app::Signal::Run => {
                 app.run_current_command();
             }
             app::Signal::AddBookmark => {
                 app.add_bookmark();
             }
+            app::Signal::UpdateBookmark => {
+                app.update_bookmark();
+            }
             app::Signal::LoadBookmark(bm) => {
                 app.load_bookmark(bm);
             }
+            app::Signal::Search => {
+                app.search_nodes();
+            }
+            app::Signal::UseHit => {
+                app.use_hit();
+            }
+            app::Signal::Yank(text) => {
+                app.yank(&text);
+            }
+            app::Signal::Resize => {
+                terminal
+                    .autoresize()
+                    .expect("Unable to resize the terminal");
+            }
             app::Signal::Nop => continue,
         }
     }
@@ -5,6 +5,41 @@ pub enum Flags {
     Stdin,
     Help,
     Version,
+    Run(Batch),
+    Invalid(String),
+}
+
+/// Non-interactive run of a bookmark or a filter.
+pub struct Batch {
+    pub target: String,
+    pub filename: Option<String>,
+    pub format: Format,
+}
+
+pub enum Format {
+    Json,
+    Compact,
+    Raw,
+}
+
+impl Format {
+    fn parse(raw: &str) -> Result<Format, String> {
+        match raw {
+            "json" => Ok(Format::Json),
+            "compact" => Ok(Format::Compact),
+            "raw" => Ok(Format::Raw),
+            other => Err(format!("Unknown output format: {}", other)),
+        }
+    }
+
+    /// The jq options producing the format.
+    pub fn options(&self) -> &'static [&'static str] {
+        match self {
+            Format::Json => &[],
+            Format::Compact => &["-c"],
+            Format::Raw => &["-r"],
+        }
+    }
 }
 
 impl Flags {
@@ -18,7 +53,39 @@ impl Flags {
             "--help" => Flags::Help,
             "-v" => Flags::Version,
             "--version" => Flags::Version,
+            "--run" => match Flags::get_batch(&args[2..]) {
+                Ok(batch) => Flags::Run(batch),
+                Err(msg) => Flags::Invalid(msg),
+            },
             filename => Flags::Filename(String::from(filename)),
         }
     }
+
+    /// Parses `BOOKMARK|FILTER [--format FORMAT] [FILE_NAME]`.
+    fn get_batch(args: &[String]) -> Result<Batch, String> {
+        let mut args = args.iter();
+        let target = args
+            .next()
+            .ok_or_else(|| String::from("Missing bookmark name or filter to run"))?;
+        let mut batch = Batch {
+            target: target.to_string(),
+            filename: None,
+            format: Format::Json,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--format" => {
+                    let format = args
+                        .next()
+                        .ok_or_else(|| String::from("Missing output format"))?;
+                    batch.format = Format::parse(format)?;
+                }
+                filename if batch.filename.is_none() => {
+                    batch.filename = Some(String::from(filename));
+                }
+                other => return Err(format!("Unexpected argument: {}", other)),
+            }
+        }
+        Ok(batch)
+    }
 }
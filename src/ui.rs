@@ -1,8 +1,9 @@
 use super::{app, help};
+use std::ops::RangeInclusive;
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Modifier, Style},
     text::{Span, Spans},
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
@@ -10,22 +11,38 @@ use tui::{
 
 pub mod panels;
 pub mod scroller;
+pub mod theme;
 
-pub use scroller::Scroller;
-pub use panels::Panel;
 pub use panels::Pane;
+pub use panels::Panel;
+pub use scroller::Scroller;
+pub use theme::Theme;
 
-const COLOR_BG: Color = Color::Black;
-const COLOR_FG: Color = Color::Rgb(184, 184, 184);
-const COLOR_FG_ACTIVE: Color = Color::Rgb(255, 255, 255);
+const MIN_WIDTH: u16 = 20;
+const MIN_CONTENT_HEIGHT: u16 = 3;
 
 pub fn draw<B: Backend>(frame: &mut Frame<B>, state: &mut app::State) {
+    let size = frame.size();
+    let min_height = state.config.layout.command_height + MIN_CONTENT_HEIGHT;
+    if size.width < MIN_WIDTH || size.height < min_height {
+        return draw_too_small(frame, MIN_WIDTH, min_height);
+    }
     match state.mode() {
         app::Mode::Help => draw_help(frame, state),
         _ => draw_app(frame, state),
     }
 }
 
+fn draw_too_small<B: Backend>(frame: &mut Frame<B>, width: u16, height: u16) {
+    let size = frame.size();
+    let message = format!(
+        "Terminal too small: {}x{}, need {}x{}",
+        size.width, size.height, width, height
+    );
+    let output = Paragraph::new(message).wrap(Wrap { trim: true });
+    frame.render_widget(output, size);
+}
+
 fn draw_help<B: Backend>(frame: &mut Frame<B>, state: &mut app::State) {
     let hparts = Layout::default()
         .direction(Direction::Horizontal)
@@ -42,7 +59,7 @@ fn draw_help<B: Backend>(frame: &mut Frame<B>, state: &mut app::State) {
         }
     }
 
-    let hlp = help::get_help();
+    let hlp = help::get_help(&state.keymap);
     for (idx, title) in hlp.keys().enumerate() {
         let help_string = &hlp[title.as_str()].join("\n");
         let output = Paragraph::new(help_string.to_string())
@@ -52,123 +69,385 @@ fn draw_help<B: Backend>(frame: &mut Frame<B>, state: &mut app::State) {
     }
 }
 
-fn draw_app<B: Backend>(frame: &mut Frame<B>, state: &mut app::State) {
-    let frame_size = frame.size();
-    let half_width = frame_size.width / 2;
-    let vert_height = frame_size.height - 3;
-    let source_size = Rect::new(frame_size.x, frame_size.y, half_width, vert_height);
-    let result_size = Rect::new(
-        frame_size.x + half_width,
-        frame_size.y,
-        half_width,
-        vert_height,
-    );
-    let cmd_size = Rect::new(
-        frame_size.x,
-        frame_size.y + vert_height,
-        frame_size.width,
-        frame_size.height - vert_height,
-    );
+/// Screen areas of the panels, shared by drawing and mouse handling.
+pub struct Areas {
+    pub source: Rect,
+    pub output: Rect,
+    pub command: Rect,
+    pub bookmarks: Rect,
+}
 
-    let content = state.source.get_content();
-    let styled = get_styled(content.as_str(), state.source.highlight);
-    let source_output = Paragraph::new(styled)
-        .block(get_block(&Panel::Source, "Source", state))
-        .scroll((state.source.get_pos(), 0))
-        .wrap(Wrap { trim: false });
+pub fn get_areas(frame_size: Rect, state: &app::State) -> Areas {
+    let layout = &state.config.layout;
+    let half_width = (frame_size.width as u32 * layout.split as u32 / 100) as u16;
+    let vert_height = frame_size.height.saturating_sub(layout.command_height);
+    let padding = 3.min(frame_size.width / 4).min(frame_size.height / 4);
+    Areas {
+        source: Rect::new(frame_size.x, frame_size.y, half_width, vert_height),
+        output: Rect::new(
+            frame_size.x + half_width,
+            frame_size.y,
+            frame_size.width.saturating_sub(half_width),
+            vert_height,
+        ),
+        command: Rect::new(
+            frame_size.x,
+            frame_size.y + vert_height,
+            frame_size.width,
+            frame_size.height.saturating_sub(vert_height),
+        ),
+        bookmarks: Rect::new(
+            frame_size.x + padding,
+            frame_size.y + padding,
+            frame_size.width.saturating_sub(padding * 2),
+            frame_size.height.saturating_sub(padding * 2),
+        ),
+    }
+}
 
-    let content = state.output.get_content();
-    let styled = get_styled(content.as_str(), state.output.highlight);
-    let result_output = Paragraph::new(styled)
-        .block(get_block(&Panel::Output, "Result", state))
-        .scroll((state.output.get_pos(), 0))
-        .wrap(Wrap { trim: false });
+fn draw_app<B: Backend>(frame: &mut Frame<B>, state: &mut app::State) {
+    let areas = get_areas(frame.size(), state);
 
     let cmd_title = match *state.mode() {
         app::Mode::Shell => "jq Command",
         app::Mode::Internal => "Internal Command",
         _ => "",
     };
-    let mut cmd = state.command().get_content();
-    let cursor = state.command().cursor();
+    let editable = get_editable(state.command(), Style::default(), &state.theme);
+    let cmd_output = Paragraph::new(Spans::from(editable))
+        .block(get_block(&Panel::Command, cmd_title, state))
+        .wrap(Wrap { trim: false });
+
+    let source_title = match state.levels.is_empty() {
+        true => String::from("Source"),
+        false => format!("Source: {}", state.breadcrumbs().join(" > ")),
+    };
+    draw_content(frame, &Panel::Source, &source_title, areas.source, state);
+    draw_content(frame, &Panel::Output, "Result", areas.output, state);
+    frame.render_widget(cmd_output, areas.command);
+
+    if let Some((title, _)) = &state.popup {
+        let title = title.to_string();
+        frame.render_widget(Clear, areas.bookmarks);
+        draw_content(frame, &Panel::Popup, &title, areas.bookmarks, state);
+        return;
+    }
+
+    if let app::Mode::Browser = state.mode() {
+        return draw_browser(frame, areas.bookmarks, state);
+    }
+
+    if let app::Mode::Bookmarks = state.mode() {
+        draw_bookmarks(frame, areas.bookmarks, state);
+    }
+}
+
+/// Draws the bookmarks overlay, scrolled to keep the current bookmark shown.
+fn draw_bookmarks<B: Backend>(frame: &mut Frame<B>, area: Rect, state: &mut app::State) {
+    let current = state.bookmarks.get_pos();
+    let width = area.width.saturating_sub(2) as usize;
+    let rows = state
+        .bookmarks
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            let line = match (idx == current, state.bookmarks.editing()) {
+                (true, Some(edit)) => {
+                    format!(
+                        "\t{}{}_",
+                        field_label(&edit.field),
+                        edit.buffer.get_content()
+                    )
+                }
+                _ => format!("\t{}{}{}", entry.markers, entry.label, entry.scope),
+            };
+            panels::content::wrapped_rows(&line, width)
+        })
+        .collect();
+    state
+        .bookmarks
+        .set_rows(rows, area.height.saturating_sub(2) as usize);
+
+    let first = state.bookmarks.offset();
+    let mut bm_items = Vec::new();
+    for (idx, entry) in state.bookmarks.entries().iter().enumerate().skip(first) {
+        let mut base = Style::default();
+        if idx == current {
+            base = state.theme.highlight(base);
+        }
+        let mut spans = vec![Span::styled("\t", base)];
+        if let (true, Some(edit)) = (idx == current, state.bookmarks.editing()) {
+            spans.push(Span::styled(field_label(&edit.field), Style::default()));
+            spans.extend(get_editable(&edit.buffer, Style::default(), &state.theme));
+            bm_items.push(Spans::from(spans));
+            continue;
+        }
+        spans.push(Span::styled(entry.markers.to_string(), base));
+        for (pos, c) in entry.label.chars().enumerate() {
+            let mut style = base;
+            if entry.matched.contains(&pos) {
+                style = style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+            }
+            spans.push(Span::styled(String::from(c), style));
+        }
+        spans.push(Span::styled(entry.scope.to_string(), base));
+        bm_items.push(Spans::from(spans));
+    }
+    let bm_title = match state.bookmarks.query() {
+        "" => String::from("Bookmarks"),
+        query => format!("Bookmarks: {}_", query),
+    };
+    let bookmarks = Paragraph::new(bm_items)
+        .block(get_block(&Panel::Bookmarks, &bm_title, state))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(Clear, area);
+    frame.render_widget(bookmarks, area);
+}
+
+fn field_label(field: &panels::bookmarks::Field) -> &'static str {
+    match field {
+        panels::bookmarks::Field::Name => "Name: ",
+        panels::bookmarks::Field::Filter => "Filter: ",
+    }
+}
+
+/// Draws the file browser overlay, scrolled to keep the current entry shown.
+fn draw_browser<B: Backend>(frame: &mut Frame<B>, area: Rect, state: &mut app::State) {
+    state
+        .browser
+        .set_height(area.height.saturating_sub(2) as usize);
+    let current = state.browser.get_pos();
+    let first = state.browser.offset();
+    let mut items = Vec::new();
+    for (idx, entry) in state.browser.entries().iter().enumerate().skip(first) {
+        let mut style = Style::default();
+        if idx == current {
+            style = state.theme.highlight(style);
+        }
+        if entry.is_dir {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        let slash = if entry.is_dir { "/" } else { "" };
+        items.push(Spans::from(Span::styled(
+            format!("{}{}", entry.name, slash),
+            style,
+        )));
+    }
+    let title = format!("Open: {}", state.browser.dir().display());
+    let browser = Paragraph::new(items).block(get_block(&Panel::Browser, &title, state));
+    frame.render_widget(Clear, area);
+    frame.render_widget(browser, area);
+}
+
+/// Draws a content panel, with the scrollbar gutter on the right.
+fn draw_content<B: Backend>(
+    frame: &mut Frame<B>,
+    panel: &Panel,
+    title: &str,
+    area: Rect,
+    state: &mut app::State,
+) {
+    let block = get_block(panel, title, state);
+    let inner = block.inner(area);
+    let text_width = inner.width.saturating_sub(1);
+    let content = match (panel, &mut state.popup) {
+        (Panel::Source, _) => &mut state.source,
+        (Panel::Popup, Some((_, popup))) => popup,
+        _ => &mut state.output,
+    };
+    content.set_viewport(text_width, inner.height);
+
+    let text = content.get_content();
+    let (pos, max, paragraph) = match content.is_structural() {
+        true => get_hits(content, inner.height, &state.theme),
+        false => get_text(&text, content, inner.height, &state.theme),
+    };
+
+    frame.render_widget(block, area);
+    frame.render_widget(
+        paragraph,
+        Rect::new(inner.x, inner.y, text_width, inner.height),
+    );
+    let gutter = Rect::new(
+        inner.x + text_width,
+        inner.y,
+        inner.width.min(1),
+        inner.height,
+    );
+    frame.render_widget(get_scrollbar(pos, max, inner.height), gutter);
+}
+
+/// Line with the selected part (all of it, without columns) highlighted.
+fn get_selected<'a>(
+    line: &'a str,
+    columns: Option<RangeInclusive<usize>>,
+    theme: &Theme,
+) -> Spans<'a> {
+    let selected = theme.highlight(Style::default());
+    let columns = match columns {
+        Some(columns) => columns,
+        None if line.is_empty() => return Spans::from(Span::styled(" ", selected)),
+        None => return Spans::from(Span::styled(line, selected)),
+    };
+    let offset = |column: usize| {
+        line.char_indices()
+            .nth(column)
+            .map_or(line.len(), |(i, _)| i)
+    };
+    let (start, end) = (offset(*columns.start()), offset(columns.end() + 1));
+    let mut spans = vec![Span::raw(&line[..start])];
+    match start < end {
+        true => spans.push(Span::styled(&line[start..end], selected)),
+        false => spans.push(Span::styled(" ", selected)),
+    }
+    spans.push(Span::raw(&line[end..]));
+    Spans::from(spans)
+}
+
+/// Visible part of the content, with its position and the last one.
+fn get_text<'a>(
+    text: &'a str,
+    content: &panels::Content,
+    height: u16,
+    theme: &Theme,
+) -> (usize, usize, Paragraph<'a>) {
+    let pos = content.get_pos();
+    let visible: Vec<&str> = text.split('\n').skip(pos).take(height as usize).collect();
+    let styled = get_styled(&visible, pos, content, theme);
+    let mut paragraph = Paragraph::new(styled).scroll((0, content.get_hpos()));
+    if content.wrap {
+        paragraph = paragraph.wrap(Wrap { trim: false });
+    }
+    (pos, content.max_pos(), paragraph)
+}
+
+/// Page of structural search hits, with the current one highlighted.
+fn get_hits(
+    content: &panels::Content,
+    height: u16,
+    theme: &Theme,
+) -> (usize, usize, Paragraph<'static>) {
+    let hits = content.hits();
+    let height = (height as usize).max(1);
+    let current = content.hit_index().unwrap_or(0);
+    let pos = current - current % height;
+    let styled: Vec<Spans> = hits
+        .iter()
+        .enumerate()
+        .skip(pos)
+        .take(height)
+        .map(|(idx, hit)| {
+            let value = match hit.is_scalar() {
+                true => hit.value.as_str(),
+                false => hit.kind.as_str(),
+            };
+            let mut style = Style::default();
+            if idx == current {
+                style = theme.highlight(style);
+            }
+            Spans::from(Span::styled(format!("{} = {}", hit.path, value), style))
+        })
+        .collect();
+    let max = hits.len().saturating_sub(height);
+    (pos.min(max), max, Paragraph::new(styled))
+}
+
+fn get_scrollbar(pos: usize, max: usize, height: u16) -> Paragraph<'static> {
+    let height = height as usize;
+    let mut thumb = 0..0;
+    if max > 0 && height > 0 {
+        let size = (height * height / (max + height)).clamp(1, height);
+        let start = pos * (height - size) / max;
+        thumb = start..start + size;
+    }
+    let rows: Vec<Spans> = (0..height)
+        .map(|row| match thumb.contains(&row) {
+            true => Spans::from("█"),
+            false => Spans::from("│"),
+        })
+        .collect();
+    Paragraph::new(rows)
+}
+
+fn get_editable(command: &panels::Command, base: Style, theme: &Theme) -> Vec<Span<'static>> {
+    let mut cmd = command.get_content();
+    let cursor = command.cursor();
     if cursor == cmd.len() {
         cmd += "_";
     }
     let mut editable = Vec::new();
     for (idx, c) in cmd.chars().enumerate() {
-        let mut style = Style::default();
+        let mut style = base;
         if idx == cursor {
-            style = style.bg(COLOR_FG).fg(COLOR_BG);
+            style = theme.highlight(style);
         }
         editable.push(Span::styled(String::from(c), style));
     }
-    let cmd_output = Paragraph::new(Spans::from(editable))
-        .block(get_block(&Panel::Command, cmd_title, state))
-        .wrap(Wrap { trim: false });
-
-    frame.render_widget(source_output, source_size);
-    frame.render_widget(result_output, result_size);
-    frame.render_widget(cmd_output, cmd_size);
-
-    if let app::Mode::Bookmarks = state.mode() {
-        let padding = 3;
-        let bm_area = Rect::new(
-            frame_size.x + padding,
-            frame_size.y + padding,
-            frame_size.width - (padding * 2),
-            frame_size.height - (padding * 2),
-        );
-
-        let bm_content = &state.bookmarks.get_content();
-        let bm_items = get_styled(bm_content, state.bookmarks.get_pos() as usize);
-        let bookmarks = Paragraph::new(bm_items)
-            .block(get_block(&Panel::Bookmarks, "Bookmarks", state))
-            .wrap(Wrap { trim: false });
-        frame.render_widget(Clear, bm_area);
-        frame.render_widget(bookmarks, bm_area);
-    }
+    editable
 }
 
-fn get_styled(content: &str, index: usize) -> Vec<Spans> {
+/// Styles the lines, starting at the first one, with all the matches marked.
+fn get_styled<'a>(
+    lines: &[&'a str],
+    first: usize,
+    content: &panels::Content,
+    theme: &Theme,
+) -> Vec<Spans<'a>> {
+    let matches = content.matches();
+    let current = content.current().map(|m| (m.line, m.start));
+    let mut found = matches.partition_point(|m| m.line < first);
     let mut styled = Vec::new();
-    for (idx, line) in content.split('\n').enumerate() {
-        let mut style = Style::default();
-        if idx == index {
-            style = style.bg(COLOR_FG).fg(COLOR_BG);
+    for (idx, line) in lines.iter().enumerate() {
+        while found < matches.len() && matches[found].line < first + idx {
+            found += 1;
+        }
+        if let Some(selection) = content.selection() {
+            if selection.lines().contains(&(first + idx)) {
+                styled.push(get_selected(line, selection.columns(), theme));
+                continue;
+            }
+        }
+        let style = Style::default();
+        let mut spans = Vec::new();
+        let mut last = 0;
+        while found < matches.len() && matches[found].line == first + idx {
+            let m = &matches[found];
+            spans.push(Span::styled(&line[last..m.start], style));
+            let matched = match current == Some((m.line, m.start)) {
+                true => theme.highlight(style),
+                false => style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            };
+            spans.push(Span::styled(&line[m.start..m.end], matched));
+            last = m.end;
+            found += 1;
         }
-        styled.push(Spans::from(vec![Span::styled(line, style)]));
+        spans.push(Span::styled(&line[last..], style));
+        styled.push(Spans::from(spans));
     }
     styled
 }
 
 fn get_block(panel: &Panel, title: &str, state: &app::State) -> Block<'static> {
     let title = get_title(panel, title, state);
-    let fg: Color = match panel {
-        Panel::Source => match state.get_active().get_type() {
-            Panel::Source => COLOR_FG_ACTIVE,
-            _ => COLOR_FG,
-        },
-        Panel::Output => match state.get_active().get_type() {
-            Panel::Output => COLOR_FG_ACTIVE,
-            _ => COLOR_FG,
-        },
-        Panel::Command => match state.get_active().get_type() {
-            Panel::Command => COLOR_FG_ACTIVE,
-            _ => COLOR_FG,
-        },
-        Panel::Help => COLOR_FG_ACTIVE,
-        Panel::Bookmarks => COLOR_FG_ACTIVE,
+    let active = match panel {
+        Panel::Source | Panel::Output | Panel::Command => state.get_active().get_type() == panel,
+        Panel::Help | Panel::Bookmarks | Panel::Browser | Panel::Popup => true,
     };
     Block::default()
         .title(String::from(" ") + &title + " ")
         .borders(Borders::ALL)
-        .style(Style::default().fg(fg).bg(COLOR_BG))
+        .style(state.theme.block(active))
 }
 
 fn get_title(panel: &Panel, title: &str, state: &app::State) -> String {
     match panel {
         Panel::Source => state.source.get_title(title),
         Panel::Output => state.output.get_title(title),
+        Panel::Popup => match &state.popup {
+            Some((_, popup)) => popup.get_title(title),
+            None => title.to_string(),
+        },
         _ => title.to_string(),
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,42 @@
-use super::super::super::{app, ui};
+use super::super::super::{app, bookmarks, ui};
+use bookmarks::{Bookmark, Origin};
 use crossterm::event::{KeyCode, KeyModifiers};
 
-use super::Scroller;
+use super::{Action, Command, Pane, Scroller};
 
 pub struct Bookmarks {
-    items: Vec<String>,
+    items: Vec<Bookmark>,
+    visible: Vec<Entry>,
+    filename: String,
+    query: String,
     scroll: Scroller,
+    offset: usize,
+    /// Screen rows taken by each visible bookmark, and shown in all, as last drawn.
+    rows: Vec<usize>,
+    height: usize,
+    edit: Option<Edit>,
+    error: Option<String>,
+}
+
+pub enum Field {
+    Name,
+    Filter,
+}
+
+/// Inline edit of the current bookmark.
+pub struct Edit {
+    pub field: Field,
+    pub buffer: Command,
+}
+
+/// A bookmark shown in the overlay, with the label characters matched by the query.
+pub struct Entry {
+    item: usize,
+    pub markers: String,
+    pub label: String,
+    pub matched: Vec<usize>,
+    /// The file scope, shown after the label.
+    pub scope: String,
 }
 
 impl Default for Bookmarks {
@@ -16,26 +47,104 @@ impl Default for Bookmarks {
 
 impl Bookmarks {
     pub fn new() -> Self {
-        let mut items = Vec::new();
-        let mut scroll = Scroller::new(0);
-        for item in load_bookmarks() {
-            items.push(item);
+        let (items, errors) = bookmarks::load();
+        let mut bms = Self {
+            items,
+            visible: Vec::new(),
+            filename: String::from(""),
+            query: String::from(""),
+            scroll: Scroller::new(0),
+            offset: 0,
+            rows: Vec::new(),
+            height: 0,
+            edit: None,
+            error: None,
+        };
+        if !errors.is_empty() {
+            bms.error = Some(errors.join("; "));
         }
-        if items.len() > 1 {
-            scroll.set_max(items.len() - 1);
-        }
-        Self { items, scroll }
+        bms.refresh();
+        bms
+    }
+
+    /// Limits the visible bookmarks to the ones scoped to the file.
+    pub fn set_filename(&mut self, filename: &str) {
+        self.filename = filename.to_string();
+        self.refresh();
     }
 
+    /// Adds a bookmark, unless there's one with the same filter already.
     pub fn add(&mut self, bm: &str) {
-        if !self.items.contains(&String::from(bm)) {
-            self.items.push(String::from(bm));
-            save_bookmarks(&self.items);
+        if self.items.iter().any(|item| item.filter == bm) {
+            return;
+        }
+        self.items.push(Bookmark::new(bm));
+        self.save();
+        self.refresh();
+    }
+
+    /// Adds a bookmark, or updates the meta data of the one with the same
+    /// filter, with the fields given only.
+    pub fn insert(&mut self, bm: Bookmark) {
+        match self.items.iter_mut().find(|item| item.filter == bm.filter) {
+            Some(item) => {
+                if !bm.name.is_empty() {
+                    item.name = bm.name;
+                }
+                if !bm.description.is_empty() {
+                    item.description = bm.description;
+                }
+                if !bm.tags.is_empty() {
+                    item.tags = bm.tags;
+                }
+                if bm.scope.is_some() {
+                    item.scope = bm.scope;
+                }
+            }
+            None => self.items.push(bm),
+        }
+        self.save();
+        self.refresh();
+    }
+
+    /// Adds the bookmarks not already present, returns the count of added ones
+    /// and the names of the ones clashing with a differing existing bookmark.
+    pub fn merge(&mut self, bms: Vec<Bookmark>) -> (usize, Vec<String>) {
+        let mut added = 0;
+        let mut conflicts = Vec::new();
+        for bm in bms {
+            if self.items.iter().any(|item| item.filter == bm.filter) {
+                continue;
+            }
+            if !bm.name.is_empty() && self.items.iter().any(|item| item.name == bm.name) {
+                conflicts.push(bm.name);
+                continue;
+            }
+            self.items.push(Bookmark {
+                origin: Origin::User,
+                ..bm
+            });
+            added += 1;
+        }
+        if added > 0 {
+            self.save();
+            self.refresh();
+        }
+        (added, conflicts)
+    }
+
+    /// The last error loading or saving the bookmarks, if not taken yet.
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+
+    fn save(&mut self) {
+        if let Err(msg) = bookmarks::save(&self.items) {
+            self.error = Some(msg);
         }
-        self.scroll.set_max(self.items.len() - 1);
     }
 
-    pub fn items(&self) -> &Vec<String> {
+    pub fn items(&self) -> &Vec<Bookmark> {
         &self.items
     }
 
@@ -47,32 +156,242 @@ impl Bookmarks {
         self.del(self.scroll.get())
     }
 
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn entries(&self) -> &Vec<Entry> {
+        &self.visible
+    }
+
+    /// Index of the first bookmark shown.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Keeps the current bookmark within the rows shown, given the rows each
+    /// visible bookmark takes.
+    pub fn set_rows(&mut self, rows: Vec<usize>, height: usize) {
+        self.rows = rows;
+        self.height = height;
+        self.fit();
+    }
+
+    fn fit(&mut self) {
+        let pos = self.scroll.get();
+        if pos < self.offset {
+            self.offset = pos;
+        }
+        if pos >= self.rows.len() {
+            return;
+        }
+        while self.offset < pos && self.rows[self.offset..=pos].iter().sum::<usize>() > self.height
+        {
+            self.offset += 1;
+        }
+    }
+
+    pub fn clear_query(&mut self) {
+        self.query = String::from("");
+        self.refresh();
+    }
+
+    /// Moves the current bookmark between the user and the project bookmarks.
+    pub fn toggle_current_origin(&mut self) -> bool {
+        if !bookmarks::has_project() {
+            return false;
+        }
+        if let Some(entry) = self.visible.get(self.scroll.get()) {
+            let item = &mut self.items[entry.item];
+            item.origin = match item.origin {
+                Origin::User => Origin::Project,
+                Origin::Project => Origin::User,
+            };
+            self.save();
+            self.refresh();
+            return true;
+        }
+        false
+    }
+
+    /// Replaces the filter of the current bookmark.
+    pub fn update_current_item(&mut self, filter: &str) -> bool {
+        if let Some(entry) = self.visible.get(self.scroll.get()) {
+            self.items[entry.item].filter = filter.to_string();
+            self.save();
+            self.refresh();
+            return true;
+        }
+        false
+    }
+
+    pub fn toggle_current_pin(&mut self) -> bool {
+        if let Some(entry) = self.visible.get(self.scroll.get()) {
+            let idx = entry.item;
+            self.items[idx].pinned = !self.items[idx].pinned;
+            self.save();
+            self.refresh();
+            if let Some(pos) = self.visible.iter().position(|entry| entry.item == idx) {
+                self.scroll.set_position(pos);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Swaps the current bookmark with the visible one above or below it.
+    /// Bookmarks matching a query are ordered by score, so they aren't moved.
+    pub fn move_current_item(&mut self, up: bool) -> bool {
+        if !self.query.is_empty() {
+            self.error = Some(String::from("Clear the search to move bookmarks"));
+            return false;
+        }
+        let pos = self.scroll.get();
+        let other = match up {
+            true if pos > 0 => pos - 1,
+            false if pos + 1 < self.visible.len() => pos + 1,
+            _ => return false,
+        };
+        let (a, b) = (self.visible[pos].item, self.visible[other].item);
+        if self.items[a].pinned != self.items[b].pinned {
+            return false;
+        }
+        // User and project bookmarks are saved apart, their order can't mix.
+        if self.items[a].origin != self.items[b].origin {
+            self.error = Some(String::from(
+                "User and project bookmarks can't be moved past each other",
+            ));
+            return false;
+        }
+        self.items.swap(a, b);
+        self.save();
+        self.refresh();
+        self.scroll.set_position(other);
+        true
+    }
+
+    pub fn editing(&self) -> Option<&Edit> {
+        self.edit.as_ref()
+    }
+
+    fn start_edit(&mut self, field: Field) {
+        if let Some(entry) = self.visible.get(self.scroll.get()) {
+            let item = &self.items[entry.item];
+            let value = match field {
+                Field::Name => &item.name,
+                Field::Filter => &item.filter,
+            };
+            self.edit = Some(Edit {
+                field,
+                buffer: Command::new(value.to_string()),
+            });
+        }
+    }
+
+    fn finish_edit(&mut self) {
+        let edit = match self.edit.take() {
+            Some(edit) => edit,
+            None => return,
+        };
+        let value = edit.buffer.value();
+        if let Some(entry) = self.visible.get(self.scroll.get()) {
+            let item = &mut self.items[entry.item];
+            match edit.field {
+                Field::Name => item.name = value,
+                Field::Filter if !value.is_empty() => item.filter = value,
+                Field::Filter => return,
+            }
+            self.save();
+            self.refresh();
+        }
+    }
+
+    fn handle_edit_event(&mut self, code: KeyCode, modifiers: KeyModifiers) -> app::Signal {
+        match code {
+            KeyCode::Enter => self.finish_edit(),
+            KeyCode::Esc => self.edit = None,
+            _ => {
+                if let Some(edit) = self.edit.as_mut() {
+                    edit.buffer.handle_event(code, modifiers);
+                }
+            }
+        }
+        app::Signal::Nop
+    }
+
+    fn use_current_item(&mut self) -> String {
+        if let Some(entry) = self.visible.get(self.scroll.get()) {
+            self.items[entry.item].touch();
+            self.save();
+        }
+        self.get_current_item()
+    }
+
     fn get(&self, idx: usize) -> String {
-        if idx < self.items.len() {
-            return self.items[idx].as_str().to_string();
+        match self.visible.get(idx) {
+            Some(entry) => self.items[entry.item].filter.as_str().to_string(),
+            None => "".to_string(),
         }
-        "".to_string()
     }
 
     fn del(&mut self, idx: usize) -> bool {
-        if idx < self.items.len() {
-            self.items.remove(idx);
-            save_bookmarks(&self.items);
+        if let Some(entry) = self.visible.get(idx) {
+            self.items.remove(entry.item);
+            self.save();
+            self.refresh();
             return true;
         }
         false
     }
+
+    fn refresh(&mut self) {
+        let mut scored = Vec::new();
+        for (idx, item) in self.items.iter().enumerate() {
+            if !self.filename.is_empty() && !item.applies_to(&self.filename) {
+                continue;
+            }
+            let label = item.label();
+            if let Some((score, matched)) = fuzzy_match(&self.query, &label) {
+                scored.push((
+                    score,
+                    Entry {
+                        item: idx,
+                        markers: item.markers(),
+                        label,
+                        matched,
+                        scope: match &item.scope {
+                            Some(scope) => format!(" @{}", scope),
+                            None => String::new(),
+                        },
+                    },
+                ));
+            }
+        }
+        let items = &self.items;
+        scored.sort_by_key(|(score, entry)| {
+            (
+                std::cmp::Reverse(items[entry.item].pinned),
+                std::cmp::Reverse(*score),
+            )
+        });
+        self.visible = scored.into_iter().map(|(_, entry)| entry).collect();
+        self.scroll.set_max(self.visible.len().saturating_sub(1));
+    }
 }
 
 impl ui::Pane for Bookmarks {
-    fn get_pos(&self) -> u16 {
-        self.scroll.get() as u16
+    fn get_pos(&self) -> usize {
+        self.scroll.get()
     }
     fn scroll_up(&mut self) {
-        self.scroll.prev();
+        if self.edit.is_none() {
+            self.scroll.prev();
+        }
     }
     fn scroll_down(&mut self) {
-        self.scroll.next();
+        if self.edit.is_none() {
+            self.scroll.next();
+        }
     }
     fn get_type(&self) -> &ui::Panel {
         &ui::Panel::Bookmarks
@@ -80,24 +399,82 @@ impl ui::Pane for Bookmarks {
 
     fn get_content(&self) -> String {
         let mut out = String::new();
-        for bm in &self.items {
-            let item = format!("\t{}\n", bm);
+        for entry in &self.visible {
+            let item = format!("\t{}{}{}\n", entry.markers, entry.label, entry.scope);
             out.push_str(&item);
         }
         out
     }
 
+    fn accepts_text(&self) -> bool {
+        true
+    }
+
+    fn captures_keys(&self) -> bool {
+        self.edit.is_some()
+    }
+
+    fn click(&mut self, _column: u16, row: u16, _width: u16) {
+        if self.edit.is_some() {
+            return;
+        }
+        let mut top = 0;
+        for (idx, rows) in self.rows.iter().enumerate().skip(self.offset) {
+            top += rows;
+            if (row as usize) < top {
+                self.scroll.set_position(idx);
+                return;
+            }
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) -> app::Signal {
+        match action {
+            Action::DeleteBookmark => {
+                self.del_current_item();
+            }
+            Action::EditBookmark => self.start_edit(Field::Filter),
+            Action::RenameBookmark => self.start_edit(Field::Name),
+            Action::PinBookmark => {
+                self.toggle_current_pin();
+            }
+            Action::MoveBookmarkUp => {
+                self.move_current_item(true);
+            }
+            Action::MoveBookmarkDown => {
+                self.move_current_item(false);
+            }
+            Action::UpdateBookmark => return app::Signal::UpdateBookmark,
+            Action::ToggleBookmarkOrigin => {
+                self.toggle_current_origin();
+            }
+            Action::ClearSearch => self.clear_query(),
+            _ => (),
+        };
+        app::Signal::Nop
+    }
+
     fn handle_event(&mut self, code: KeyCode, modifiers: KeyModifiers) -> app::Signal {
+        if self.edit.is_some() {
+            return self.handle_edit_event(code, modifiers);
+        }
         match code {
-            KeyCode::Enter => app::Signal::LoadBookmark(self.get_current_item()),
-            KeyCode::Delete => {
-                self.del_current_item();
+            KeyCode::Enter => app::Signal::LoadBookmark(self.use_current_item()),
+            KeyCode::Esc => {
+                self.clear_query();
                 app::Signal::Nop
             }
-            KeyCode::Char('d') => {
-                if KeyModifiers::CONTROL == modifiers {
-                    self.del_current_item();
-                }
+            KeyCode::Char(c)
+                if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.query.push(c);
+                self.refresh();
+                self.scroll.set_position(0);
+                app::Signal::Nop
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refresh();
                 app::Signal::Nop
             }
             _ => app::Signal::Nop,
@@ -105,54 +482,34 @@ impl ui::Pane for Bookmarks {
     }
 }
 
-use std::io::Write;
-use std::path::{Path, PathBuf};
-
-fn get_config_path() -> PathBuf {
-    let home = std::env::var("HOME")
-        .or_else(|_| std::env::var("HOMEPATH"))
-        .expect("Unable to resolve user home directory");
-    let path = Path::new(&home).join(".config").join("ijqrs");
-
-    if !path.exists() {
-        let dir = path
-            .to_str()
-            .expect("Unable to resolve the user config directory");
-        std::fs::create_dir_all(dir).expect("Unable to create the missing config directory");
-    }
-
-    path
-}
-
-fn get_bookmarks_file_path() -> String {
-    let path = get_config_path().join("bookmarks");
-    if !path.exists() {
-        std::fs::File::create(&path).expect("Unable to create the bookmarks file");
-    }
-    path.to_str()
-        .expect("Unable to resolve bookmarks file")
-        .to_string()
-}
-
-fn load_bookmarks() -> Vec<String> {
-    let mut result = Vec::new();
-    let bm_file = get_bookmarks_file_path();
-    let raw = std::fs::read_to_string(bm_file).expect("Unable to read bookmarks file");
-    for line in raw.split('\n') {
-        if !line.is_empty() {
-            result.push(line.to_string());
+/// Case-insensitive subsequence match, scoring consecutive and early matches higher.
+fn fuzzy_match(query: &str, subject: &str) -> Option<(i64, Vec<usize>)> {
+    let mut matched = Vec::new();
+    let mut score = 0;
+    let mut wanted = query.chars().map(fold_case).peekable();
+    for (idx, c) in subject.chars().map(fold_case).enumerate() {
+        match wanted.peek() {
+            Some(w) if *w == c => {
+                score += match matched.last() {
+                    Some(last) if last + 1 == idx => 5,
+                    _ => 1,
+                };
+                if matched.is_empty() {
+                    score -= idx as i64 / 4;
+                }
+                matched.push(idx);
+                wanted.next();
+            }
+            Some(_) => (),
+            None => break,
         }
     }
-    result
+    match wanted.peek() {
+        Some(_) => None,
+        None => Some((score, matched)),
+    }
 }
 
-fn save_bookmarks(bms: &[String]) {
-    let mut bookmarks = String::new();
-    for bm in bms {
-        bookmarks.push_str(&format!("{}\n", bm));
-    }
-    let fpath = get_bookmarks_file_path();
-    let mut file = std::fs::File::create(fpath).expect("Unable to create the bookmarks file");
-    file.write_all(bookmarks.as_bytes())
-        .expect("Error writing bookmarks file");
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
 }
@@ -0,0 +1,210 @@
+use super::super::super::{app, ui};
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::Scroller;
+
+/// Directory listing overlay, to pick a file to open.
+pub struct Browser {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    scroll: Scroller,
+    offset: usize,
+    height: usize,
+}
+
+/// A directory entry, the parent directory being the `..` one.
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+impl Default for Browser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Browser {
+    pub fn new() -> Self {
+        Self {
+            dir: PathBuf::from("."),
+            entries: Vec::new(),
+            scroll: Scroller::new(0),
+            offset: 0,
+            height: 0,
+        }
+    }
+
+    /// Lists the directory, directories first.
+    pub fn open(&mut self, dir: &Path) -> Result<(), String> {
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("Error reading {}: {}", dir.display(), e))?;
+        let listing =
+            fs::read_dir(&dir).map_err(|e| format!("Error reading {}: {}", dir.display(), e))?;
+        let mut entries: Vec<Entry> = listing
+            .filter_map(|entry| entry.ok())
+            .map(|entry| Entry {
+                name: entry.file_name().to_string_lossy().to_string(),
+                is_dir: entry.path().is_dir(),
+            })
+            .collect();
+        entries.sort_by(|a, b| (!a.is_dir, &a.name).cmp(&(!b.is_dir, &b.name)));
+        if dir.parent().is_some() {
+            entries.insert(
+                0,
+                Entry {
+                    name: String::from(".."),
+                    is_dir: true,
+                },
+            );
+        }
+        self.dir = dir;
+        self.entries = entries;
+        self.scroll = Scroller::new(self.entries.len().saturating_sub(1));
+        self.offset = 0;
+        Ok(())
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn entries(&self) -> &Vec<Entry> {
+        &self.entries
+    }
+
+    /// Index of the first entry shown.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Keeps the current entry within the rows shown.
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height;
+        self.fit();
+    }
+
+    fn fit(&mut self) {
+        let pos = self.scroll.get();
+        if pos < self.offset {
+            self.offset = pos;
+        }
+        if self.height > 0 && pos >= self.offset + self.height {
+            self.offset = pos + 1 - self.height;
+        }
+    }
+
+    /// Enters the current directory, or asks for the current file to be opened.
+    fn enter(&mut self) -> app::Signal {
+        let entry = match self.entries.get(self.scroll.get()) {
+            Some(entry) => entry,
+            None => return app::Signal::Nop,
+        };
+        if entry.name == ".." {
+            self.leave();
+            return app::Signal::Nop;
+        }
+        let path = self.dir.join(&entry.name);
+        if !entry.is_dir {
+            return app::Signal::Open(relative(&path).display().to_string());
+        }
+        let _ = self.open(&path);
+        app::Signal::Nop
+    }
+
+    fn leave(&mut self) {
+        let previous = self.dir.file_name().map(|name| name.to_os_string());
+        if let Some(parent) = self.dir.parent().map(Path::to_path_buf) {
+            if self.open(&parent).is_ok() {
+                self.select_entry(previous);
+            }
+        }
+    }
+
+    /// Puts the cursor on the named entry, the directory just left.
+    fn select_entry(&mut self, name: Option<OsString>) {
+        let name = match name {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return,
+        };
+        if let Some(pos) = self.entries.iter().position(|entry| entry.name == name) {
+            self.scroll.set_position(pos);
+            self.fit();
+        }
+    }
+}
+
+/// The path relative to the working directory, when it's within it.
+fn relative(path: &Path) -> PathBuf {
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+impl ui::Pane for Browser {
+    fn get_pos(&self) -> usize {
+        self.scroll.get()
+    }
+    fn scroll_up(&mut self) {
+        self.scroll.prev();
+        self.fit();
+    }
+    fn scroll_down(&mut self) {
+        self.scroll.next();
+        self.fit();
+    }
+    fn page_up(&mut self) {
+        self.scroll.back(self.height.max(1));
+        self.fit();
+    }
+    fn page_down(&mut self) {
+        self.scroll.forward(self.height.max(1));
+        self.fit();
+    }
+    fn scroll_top(&mut self) {
+        self.scroll.set_position(0);
+        self.fit();
+    }
+    fn scroll_bottom(&mut self) {
+        self.scroll.set_position(self.scroll.max());
+        self.fit();
+    }
+    fn get_type(&self) -> &ui::Panel {
+        &ui::Panel::Browser
+    }
+
+    fn get_content(&self) -> String {
+        let mut out = String::new();
+        for entry in &self.entries {
+            out.push_str(&entry.name);
+            if entry.is_dir {
+                out.push('/');
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn click(&mut self, _column: u16, row: u16, _width: u16) {
+        self.scroll.set_position(self.offset + row as usize);
+    }
+
+    fn handle_event(&mut self, code: KeyCode, _modifiers: KeyModifiers) -> app::Signal {
+        match code {
+            KeyCode::Enter | KeyCode::Right => return self.enter(),
+            KeyCode::Backspace | KeyCode::Left => self.leave(),
+            KeyCode::PageUp => self.page_up(),
+            KeyCode::PageDown => self.page_down(),
+            KeyCode::Home => self.scroll_top(),
+            KeyCode::End => self.scroll_bottom(),
+            _ => (),
+        }
+        app::Signal::Nop
+    }
+}
@@ -1,7 +1,8 @@
-use super::super::super::{app, ui};
+use super::super::super::{app, config, ui};
 use crossterm::event::{KeyCode, KeyModifiers};
 
 use super::Scroller;
+use std::path::PathBuf;
 
 pub struct Command {
     scroll: Scroller,
@@ -9,6 +10,7 @@ pub struct Command {
     history: Vec<String>,
     command: String,
     status: app::Status,
+    history_file: Option<PathBuf>,
 }
 
 impl Command {
@@ -23,30 +25,66 @@ impl Command {
             history: vec![command.as_str().to_string()],
             command,
             status: app::Status::Ok,
+            history_file: None,
         }
     }
 
+    /// Keeps the command history in a file, restoring the previously recorded entries.
+    pub fn with_history(command: String, path: PathBuf) -> Command {
+        let mut cmd = Command::new(command);
+        let mut history = config::load_history(&path);
+        if history.last() != Some(&cmd.command) {
+            history.push(cmd.command.to_string());
+        }
+        cmd.history = history;
+        cmd.scroll.set_max(cmd.history.len() - 1);
+        cmd.scroll.set_position(cmd.scroll.max());
+        cmd.history_file = Some(path);
+        cmd
+    }
+
     pub fn clear(&mut self) {
         self.command = String::from("");
         self.tail_cursor();
     }
 
     pub fn record(&mut self) {
+        if self.history.last() == Some(&self.command) {
+            self.scroll.set_position(self.scroll.max());
+            return;
+        }
         self.history.push(self.command.to_string());
         self.scroll.set_max(self.history.len() - 1);
         self.scroll.set_position(self.scroll.max());
+        let saved = match &self.history_file {
+            Some(path) => config::save_history(path, &self.history),
+            None => Ok(()),
+        };
+        if let Err(msg) = saved {
+            self.set_error(&msg);
+        }
     }
 
     pub fn set_error(&mut self, error: &str) {
         self.status = app::Status::Error(error.to_string());
     }
 
+    pub fn set_info(&mut self, info: &str) {
+        self.status = app::Status::Info(info.to_string());
+    }
+
+    /// The command itself, regardless of any status shown in its place.
+    pub fn value(&self) -> String {
+        self.command.as_str().to_string()
+    }
+
     pub fn cursor(&self) -> usize {
         self.cursor.get()
     }
 
     pub fn replace(&mut self, with: String) {
         self.command = with;
+        self.tail_cursor();
     }
 
     fn prev_from_history(&mut self) {
@@ -124,12 +162,13 @@ impl Command {
 }
 
 impl ui::Pane for Command {
-    fn get_pos(&self) -> u16 {
-        self.scroll.get() as u16
+    fn get_pos(&self) -> usize {
+        self.scroll.get()
     }
     fn get_content(&self) -> String {
         match &self.status {
             app::Status::Error(err) => err.as_str().to_string(),
+            app::Status::Info(info) => info.as_str().to_string(),
             app::Status::Ok => self.command.as_str().to_string(),
         }
     }
@@ -143,6 +182,17 @@ impl ui::Pane for Command {
         self.next_from_history();
     }
 
+    fn accepts_text(&self) -> bool {
+        true
+    }
+
+    fn click(&mut self, column: u16, row: u16, width: u16) {
+        if let app::Status::Ok = self.status {
+            let pos = row as usize * width as usize + column as usize;
+            self.cursor.set_position(pos.min(self.command.len()));
+        }
+    }
+
     fn handle_event(&mut self, code: KeyCode, _modifiers: KeyModifiers) -> app::Signal {
         match code {
             KeyCode::Char(c) => {
@@ -157,6 +207,9 @@ impl ui::Pane for Command {
             KeyCode::Enter => {
                 return app::Signal::Run;
             }
+            KeyCode::Tab => {
+                return app::Signal::Complete;
+            }
             KeyCode::Left => {
                 self.cursor.prev();
             }
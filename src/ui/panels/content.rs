@@ -1,97 +1,539 @@
 use super::super::super::{app, ui};
 use crossterm::event::{KeyCode, KeyModifiers};
 
-use super::Scroller;
+use regex::{Regex, RegexBuilder};
+use std::ops::RangeInclusive;
+
+use super::{Action, Pane, Scroller};
+
+const HSCROLL_STEP: usize = 4;
 
 pub struct Content {
     kind: ui::Panel,
     pub mode: PatternMode,
     pattern: String,
-    pub highlight: usize,
+    pub options: SearchOptions,
+    matches: Vec<Match>,
+    nodes: Vec<Node>,
+    hits: Vec<usize>,
+    current: Option<usize>,
+    selection: Option<Selection>,
+    invalid: bool,
+    wrapped: bool,
+    history: Vec<String>,
+    recall: Option<usize>,
+    pub wrap: bool,
     scroll: Scroller,
+    hscroll: Scroller,
+    viewport: (u16, u16),
     content: String,
 }
 
 impl Content {
     pub fn new(content: String, kind: ui::Panel) -> Content {
-        let s = Scroller::new(content.split('\n').count());
+        let s = Scroller::new(content.split('\n').count().saturating_sub(1));
         Content {
             kind,
             mode: PatternMode::None,
             pattern: String::from(""),
-            highlight: 0,
+            options: SearchOptions::default(),
+            matches: Vec::new(),
+            nodes: Vec::new(),
+            hits: Vec::new(),
+            current: None,
+            selection: None,
+            invalid: false,
+            wrapped: false,
+            history: Vec::new(),
+            recall: None,
+            wrap: true,
             scroll: s,
+            hscroll: Scroller::new(0),
+            viewport: (0, 0),
             content: String::from(content.as_str()),
         }
     }
 
+    /// Replaces the content, keeping the display and search settings.
+    pub fn replace(&mut self, content: String) {
+        self.scroll = Scroller::new(content.split('\n').count().saturating_sub(1));
+        self.hscroll = Scroller::new(0);
+        self.viewport = (0, 0);
+        self.content = content;
+        self.selection = None;
+        self.reset_search();
+    }
+
+    /// Replaces the content, keeping the scroll positions where possible.
+    pub fn reload(&mut self, content: String) {
+        let (pos, hpos) = (self.scroll.get(), self.hscroll.get());
+        self.replace(content);
+        self.scroll.set_position(pos.min(self.scroll.max()));
+        // Both get clamped to the viewport on the next draw.
+        self.hscroll.set_max(hpos);
+        self.hscroll.set_position(hpos);
+    }
+
+    pub fn selection(&self) -> Option<&Selection> {
+        self.selection.as_ref()
+    }
+
+    /// Selected text, if there's a selection.
+    pub fn selected(&self) -> Option<String> {
+        let selection = self.selection.as_ref()?;
+        let lines: Vec<String> = self
+            .content
+            .split('\n')
+            .enumerate()
+            .filter(|(idx, _)| selection.lines().contains(idx))
+            .map(|(_, line)| match selection.columns() {
+                Some(columns) => line
+                    .chars()
+                    .skip(*columns.start())
+                    .take(columns.end() + 1 - columns.start())
+                    .collect(),
+                None => line.to_string(),
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Starts the selection at the top visible line, or ends the current one.
+    /// Columns start from the first one shown, when scrolled sideways.
+    fn toggle_selection(&mut self, kind: SelectionKind) {
+        if let Some(selection) = &self.selection {
+            if selection.kind == kind {
+                self.selection = None;
+                return;
+            }
+        }
+        let column = self.hscroll.get();
+        let (anchor, cursor) = match &self.selection {
+            Some(selection) => (selection.anchor.0, selection.cursor.0),
+            None => {
+                let line = self.current().map_or(self.scroll.get(), |m| m.line);
+                (line, line)
+            }
+        };
+        self.selection = Some(Selection {
+            kind,
+            anchor: (anchor, column),
+            cursor: (cursor, column),
+        });
+    }
+
+    /// Moves the selection cursor by lines when selecting, scrolls otherwise.
+    fn step(&mut self, forward: bool, by: usize) {
+        let last = self.lines() - 1;
+        let selection = match &mut self.selection {
+            Some(selection) => selection,
+            None => {
+                match forward {
+                    true => self.scroll.forward(by),
+                    false => self.scroll.back(by),
+                }
+                return;
+            }
+        };
+        selection.cursor.0 = match forward {
+            true => selection.cursor.0.saturating_add(by).min(last),
+            false => selection.cursor.0.saturating_sub(by),
+        };
+        let line = selection.cursor.0;
+        let pos = self.scroll.get();
+        if line < pos {
+            self.scroll_to(line);
+        } else if line >= pos + self.page() {
+            self.scroll_to(line + 1 - self.page());
+        }
+    }
+
+    /// Moves the block selection cursor by columns, scrolls otherwise.
+    fn step_column(&mut self, forward: bool) {
+        if let Some(selection) = &mut self.selection {
+            if selection.kind == SelectionKind::Block {
+                selection.cursor.1 = match forward {
+                    true => selection.cursor.1 + 1,
+                    false => selection.cursor.1.saturating_sub(1),
+                };
+                let column = selection.cursor.1;
+                if !self.wrap {
+                    self.show_column(column);
+                }
+                return;
+            }
+        }
+        if !self.wrap {
+            match forward {
+                true => self.hscroll.forward(HSCROLL_STEP),
+                false => self.hscroll.back(HSCROLL_STEP),
+            }
+        }
+    }
+
+    /// Scrolls sideways as little as needed for the column to be shown.
+    fn show_column(&mut self, column: usize) {
+        let (first, width) = (self.hscroll.get(), self.viewport.0 as usize);
+        if column < first {
+            self.hscroll.set_position(column);
+        } else if width > 0 && column >= first + width {
+            self.hscroll.set_position(column + 1 - width);
+        }
+    }
+
+    /// Inner size of the panel text area, as last drawn.
+    pub fn set_viewport(&mut self, width: u16, height: u16) {
+        if self.viewport != (width, height) {
+            self.viewport = (width, height);
+            self.fit();
+        }
+    }
+
+    pub fn lines(&self) -> usize {
+        self.content.split('\n').count()
+    }
+
+    /// Last position that still fills the whole viewport.
+    pub fn max_pos(&self) -> usize {
+        self.scroll.max()
+    }
+
+    /// Keeps the scrolling within the content, as it fits the viewport.
+    fn fit(&mut self) {
+        let (width, height) = (self.viewport.0 as usize, self.viewport.1 as usize);
+        let lines: Vec<&str> = self.content.split('\n').collect();
+        let mut rows = 0;
+        let mut start = lines.len();
+        while start > 0 {
+            rows += match self.wrap {
+                true => wrapped_rows(lines[start - 1], width),
+                false => 1,
+            };
+            if rows > height {
+                break;
+            }
+            start -= 1;
+        }
+        self.scroll.set_max(start);
+
+        let longest = lines.iter().map(|l| l.chars().count()).max();
+        self.hscroll
+            .set_max(longest.unwrap_or(0).saturating_sub(width));
+    }
+
+    pub fn get_hpos(&self) -> u16 {
+        match self.wrap {
+            true => 0,
+            false => self.hscroll.get() as u16,
+        }
+    }
+
+    fn page(&self) -> usize {
+        (self.viewport.1 as usize).max(1)
+    }
+
     pub fn pattern(&self) -> String {
         self.pattern.as_str().to_string()
     }
 
+    pub fn get_position(&self) -> String {
+        let percent = match self.max_pos() {
+            0 => 100,
+            max => self.get_pos() * 100 / max,
+        };
+        format!(
+            "line {}/{} ({}%)",
+            self.get_pos() + 1,
+            self.lines(),
+            percent
+        )
+    }
+
     pub fn get_title(&self, base: &str) -> String {
         let suffix = match self.mode {
             PatternMode::None => String::from(""),
             PatternMode::Receiving => {
-                format!(": {}_", self.pattern())
+                format!(": {}_{}", self.pattern(), self.get_flags())
             }
             PatternMode::Matching => {
-                format!(": [{}] <{}>", self.pattern(), self.highlight)
+                let current = self.current.map_or(0, |idx| idx + 1);
+                let wrapped = match self.wrapped {
+                    true => " - search wrapped",
+                    false => "",
+                };
+                format!(
+                    ": [{}] {}/{}{}{}",
+                    self.pattern(),
+                    current,
+                    self.count(),
+                    self.get_flags(),
+                    wrapped
+                )
             }
         };
-        format!("{}{}", base, suffix)
+        format!("{} {}{}", base, self.get_position(), suffix)
+    }
+
+    fn get_flags(&self) -> String {
+        let mut flags = Vec::new();
+        if self.options.regex {
+            flags.push("regex");
+        }
+        if !self.options.smart_case {
+            flags.push("case");
+        }
+        if self.options.whole_word {
+            flags.push("word");
+        }
+        if self.invalid {
+            flags.push("invalid");
+        }
+        match flags.is_empty() {
+            true => String::from(""),
+            false => format!(" ({})", flags.join(", ")),
+        }
+    }
+
+    /// All the pattern matches, ordered by position.
+    pub fn matches(&self) -> &[Match] {
+        &self.matches
+    }
+
+    pub fn current(&self) -> Option<&Match> {
+        match self.is_structural() {
+            true => None,
+            false => self.current.map(|idx| &self.matches[idx]),
+        }
+    }
+
+    /// Structural search target and pattern, picked with the pattern prefix.
+    pub fn structure(&self) -> Option<(Structure, &str)> {
+        let prefixes = [
+            ("key:", Structure::Key),
+            ("value:", Structure::Value),
+            ("type:", Structure::Type),
+        ];
+        prefixes.into_iter().find_map(|(prefix, structure)| {
+            self.pattern
+                .strip_prefix(prefix)
+                .map(|pattern| (structure, pattern))
+        })
+    }
+
+    /// Whether the structural search hits are shown instead of the content.
+    pub fn is_structural(&self) -> bool {
+        matches!(self.mode, PatternMode::Matching) && self.structure().is_some()
+    }
+
+    pub fn hits(&self) -> Vec<&Node> {
+        self.hits.iter().map(|idx| &self.nodes[*idx]).collect()
+    }
+
+    pub fn current_hit(&self) -> Option<&Node> {
+        self.hit_index().map(|idx| &self.nodes[self.hits[idx]])
+    }
+
+    pub fn hit_index(&self) -> Option<usize> {
+        match self.is_structural() {
+            true => self.current,
+            false => None,
+        }
+    }
+
+    /// Nodes to look through with the structural search, as listed by jq.
+    pub fn set_nodes(&mut self, nodes: Vec<Node>) {
+        self.nodes = nodes;
+        self.update_matches();
+        self.find_next();
+    }
+
+    fn count(&self) -> usize {
+        match self.structure() {
+            Some(_) => self.hits.len(),
+            None => self.matches.len(),
+        }
     }
 
     fn push(&mut self, c: char) {
         self.pattern.push(c);
+        self.update_matches();
     }
 
     fn pop(&mut self) {
         self.pattern.pop();
+        self.update_matches();
+    }
+
+    /// Smart case: case insensitive, unless the pattern has upper case letters.
+    fn get_matcher(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        let insensitive = self.options.smart_case && !pattern.chars().any(char::is_uppercase);
+        let mut pattern = match self.options.regex {
+            true => pattern.to_string(),
+            false => regex::escape(pattern),
+        };
+        if self.options.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+        RegexBuilder::new(&pattern)
+            .case_insensitive(insensitive)
+            .build()
+    }
+
+    fn update_matches(&mut self) {
+        self.matches.clear();
+        self.current = None;
+        self.invalid = false;
+        self.wrapped = false;
+        self.hits.clear();
+        let (structure, pattern) = match self.structure() {
+            Some((structure, pattern)) => (Some(structure), pattern),
+            None => (None, self.pattern.as_str()),
+        };
+        if pattern.is_empty() {
+            return;
+        }
+        let matcher = match self.get_matcher(pattern) {
+            Ok(matcher) => matcher,
+            Err(_) => {
+                self.invalid = true;
+                return;
+            }
+        };
+        if let Some(structure) = structure {
+            let pattern = pattern.to_lowercase();
+            self.hits = (0..self.nodes.len())
+                .filter(|idx| {
+                    let node = &self.nodes[*idx];
+                    match structure {
+                        Structure::Key => !node.key.is_empty() && matcher.is_match(&node.key),
+                        Structure::Value => node.is_scalar() && matcher.is_match(&node.value),
+                        Structure::Type => node.kind == pattern,
+                    }
+                })
+                .collect();
+            return;
+        }
+        for (line, text) in self.content.split('\n').enumerate() {
+            for found in matcher.find_iter(text) {
+                if found.start() < found.end() {
+                    self.matches.push(Match {
+                        line,
+                        start: found.start(),
+                        end: found.end(),
+                    });
+                }
+            }
+        }
     }
 
+    fn select(&mut self, idx: usize, wrapped: bool) {
+        self.current = Some(idx);
+        self.wrapped = wrapped;
+        if !self.is_structural() {
+            self.scroll_to(self.matches[idx].line);
+        }
+    }
+
+    /// Goes to the next match, wrapping around to the first one.
     fn find_next(&mut self) {
-        for (count, line) in self.content.split('\n').enumerate() {
-            if line.contains(&self.pattern) && count > self.scroll.get() {
-                self.scroll.set_position(count);
-                self.highlight = count;
-                break;
+        let count = self.count();
+        if count == 0 {
+            return;
+        }
+        let next = match self.current {
+            Some(idx) => Some(idx + 1).filter(|next| *next < count),
+            None if self.is_structural() => Some(0),
+            None => {
+                let pos = self.scroll.get();
+                self.matches.iter().position(|m| m.line >= pos)
             }
+        };
+        match next {
+            Some(idx) => self.select(idx, false),
+            None => self.select(0, true),
         }
     }
 
+    /// Goes to the previous match, wrapping around to the last one.
     fn find_prev(&mut self) {
-        let mut lines: Vec<&str> = self.content.split('\n').collect();
-        let mut count = lines.len();
-        lines.reverse();
-        for line in lines {
-            count -= 1;
-            if line.contains(&self.pattern) && count < self.scroll.get() {
-                self.scroll.set_position(count);
-                self.highlight = count;
-                break;
+        let count = self.count();
+        if count == 0 {
+            return;
+        }
+        let prev = match self.current {
+            Some(idx) => idx.checked_sub(1),
+            None if self.is_structural() => None,
+            None => {
+                let pos = self.scroll.get();
+                self.matches.iter().rposition(|m| m.line <= pos)
             }
+        };
+        match prev {
+            Some(idx) => self.select(idx, false),
+            None => self.select(count - 1, true),
         }
     }
 
+    fn record(&mut self) {
+        self.recall = None;
+        if self.pattern.is_empty() || self.history.last() == Some(&self.pattern) {
+            return;
+        }
+        self.history.push(self.pattern());
+    }
+
+    /// Recalls the previous (or next, when going forward) pattern from history.
+    fn recall(&mut self, forward: bool) {
+        let recall = match (self.recall, forward) {
+            (None, false) => self.history.len().checked_sub(1),
+            (None, true) => None,
+            (Some(idx), false) => Some(idx.saturating_sub(1)),
+            (Some(idx), true) => Some(idx + 1).filter(|next| *next < self.history.len()),
+        };
+        self.recall = recall;
+        self.pattern = match recall {
+            Some(idx) => self.history[idx].to_string(),
+            None => String::from(""),
+        };
+        self.update_matches();
+    }
+
+    fn toggle_option(&mut self, action: Action) {
+        match action {
+            Action::ToggleRegex => self.options.regex = !self.options.regex,
+            Action::ToggleSmartCase => self.options.smart_case = !self.options.smart_case,
+            Action::ToggleWholeWord => self.options.whole_word = !self.options.whole_word,
+            _ => return,
+        }
+        self.update_matches();
+    }
+
     fn reset_search(&mut self) {
         self.mode = PatternMode::None;
         self.pattern = String::from("");
-        self.highlight = 0;
+        self.recall = None;
+        self.nodes.clear();
+        self.update_matches();
     }
 }
 
 impl ui::Pane for Content {
     fn scroll_up(&mut self) {
-        self.scroll.prev();
+        match self.mode {
+            _ if self.is_structural() => self.find_prev(),
+            _ => self.step(false, 1),
+        }
     }
 
     fn scroll_down(&mut self) {
-        self.scroll.next();
+        match self.mode {
+            _ if self.is_structural() => self.find_next(),
+            _ => self.step(true, 1),
+        }
     }
 
-    fn get_pos(&self) -> u16 {
-        self.scroll.get() as u16
+    fn get_pos(&self) -> usize {
+        self.scroll.get()
     }
 
     fn get_type(&self) -> &ui::Panel {
@@ -102,7 +544,103 @@ impl ui::Pane for Content {
         self.content.as_str().to_string()
     }
 
-    fn handle_event(&mut self, code: KeyCode, modifiers: KeyModifiers) -> app::Signal {
+    fn accepts_text(&self) -> bool {
+        matches!(self.mode, PatternMode::Receiving)
+    }
+
+    /// Up and Down recall the search history while typing the pattern.
+    fn captures_key(&self, code: KeyCode) -> bool {
+        matches!(self.mode, PatternMode::Receiving) && matches!(code, KeyCode::Up | KeyCode::Down)
+    }
+
+    fn page_up(&mut self) {
+        self.step(false, self.page());
+    }
+
+    fn page_down(&mut self) {
+        self.step(true, self.page());
+    }
+
+    fn half_page_up(&mut self) {
+        self.step(false, (self.page() / 2).max(1));
+    }
+
+    fn half_page_down(&mut self) {
+        self.step(true, (self.page() / 2).max(1));
+    }
+
+    fn scroll_top(&mut self) {
+        self.step(false, usize::MAX);
+    }
+
+    fn scroll_bottom(&mut self) {
+        self.step(true, usize::MAX);
+    }
+
+    fn scroll_to(&mut self, line: usize) {
+        self.scroll.set_position(line.min(self.scroll.max()));
+    }
+
+    fn scroll_left(&mut self) {
+        self.step_column(false);
+    }
+
+    fn scroll_right(&mut self) {
+        self.step_column(true);
+    }
+
+    fn toggle_wrap(&mut self) {
+        self.wrap = !self.wrap;
+        self.fit();
+    }
+
+    fn handle_action(&mut self, action: Action) -> app::Signal {
+        match action {
+            Action::Search => {
+                if let PatternMode::None = self.mode {
+                    self.mode = PatternMode::Receiving
+                }
+            }
+            Action::SearchNext => {
+                if let PatternMode::Matching = self.mode {
+                    self.find_next();
+                }
+            }
+            Action::SearchPrev => {
+                if let PatternMode::Matching = self.mode {
+                    self.find_prev();
+                }
+            }
+            Action::ClearSearch => self.reset_search(),
+            Action::Yank => {
+                let text = match (self.selected(), self.current_hit()) {
+                    (Some(selected), _) => selected,
+                    (None, Some(hit)) => hit.path.to_string(),
+                    (None, None) => self.get_content(),
+                };
+                return app::Signal::Yank(text);
+            }
+            Action::SelectLines => self.toggle_selection(SelectionKind::Line),
+            Action::SelectBlock => self.toggle_selection(SelectionKind::Block),
+            Action::UseHit if self.current_hit().is_some() => return app::Signal::UseHit,
+            Action::ToggleRegex | Action::ToggleSmartCase | Action::ToggleWholeWord => {
+                self.toggle_option(action)
+            }
+            Action::PageUp => self.page_up(),
+            Action::PageDown => self.page_down(),
+            Action::HalfPageUp => self.half_page_up(),
+            Action::HalfPageDown => self.half_page_down(),
+            Action::ScrollTop => self.scroll_top(),
+            Action::ScrollBottom => self.scroll_bottom(),
+            Action::ScrollLeft => self.scroll_left(),
+            Action::ScrollRight => self.scroll_right(),
+            Action::ToggleWrap => self.toggle_wrap(),
+            _ => (),
+        };
+        app::Signal::Nop
+    }
+
+    fn handle_event(&mut self, code: KeyCode, _modifiers: KeyModifiers) -> app::Signal {
         match code {
             KeyCode::Esc => {
                 if let PatternMode::Matching = self.mode {
@@ -112,6 +650,10 @@ impl ui::Pane for Content {
             KeyCode::Enter => match self.mode {
                 PatternMode::Receiving => {
                     self.mode = PatternMode::Matching;
+                    self.record();
+                    if self.structure().is_some() {
+                        return app::Signal::Search;
+                    }
                     self.find_next();
                 }
                 PatternMode::Matching => {
@@ -124,43 +666,117 @@ impl ui::Pane for Content {
                     self.pop()
                 }
             }
-            KeyCode::Char('/') => {
-                if let PatternMode::None = self.mode {
-                    self.mode = PatternMode::Receiving
-                }
-            }
-            KeyCode::Char('l') => {
-                if modifiers == KeyModifiers::CONTROL {
-                    self.reset_search()
-                } else if let PatternMode::Receiving = self.mode {
-                    self.push('l');
-                }
-            }
-            KeyCode::Char('n') => match self.mode {
-                PatternMode::Receiving => self.push('n'),
-                PatternMode::Matching => {
-                    self.find_next();
-                }
-                _ => (),
-            },
-            KeyCode::Char('N') => match self.mode {
-                PatternMode::Receiving => self.push('N'),
-                PatternMode::Matching => {
-                    self.find_prev();
-                }
-                _ => (),
-            },
             KeyCode::Char(c) => {
                 if let PatternMode::Receiving = self.mode {
                     self.push(c)
                 }
             }
+            KeyCode::Up | KeyCode::Down => {
+                if let PatternMode::Receiving = self.mode {
+                    self.recall(code == KeyCode::Down)
+                }
+            }
             _ => (),
         };
         app::Signal::Nop
     }
 }
 
+/// Number of screen rows the line takes when word wrapped to width.
+pub fn wrapped_rows(line: &str, width: usize) -> usize {
+    if width == 0 {
+        return 1;
+    }
+    let mut rows = 1;
+    let mut column = 0;
+    for word in line.split_inclusive(' ') {
+        let visible = word.trim_end_matches(' ').chars().count();
+        if column > 0 && column + visible > width {
+            rows += 1;
+            column = 0;
+        }
+        column += word.chars().count();
+        while column > width {
+            rows += 1;
+            column -= width;
+        }
+    }
+    rows
+}
+
+pub struct SearchOptions {
+    pub regex: bool,
+    pub smart_case: bool,
+    pub whole_word: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            regex: false,
+            smart_case: true,
+            whole_word: false,
+        }
+    }
+}
+
+/// Pattern match, as a byte range within the line.
+pub struct Match {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SelectionKind {
+    Line,
+    Block,
+}
+
+/// Selection between the anchor and the cursor, as (line, column) pairs.
+pub struct Selection {
+    pub kind: SelectionKind,
+    anchor: (usize, usize),
+    cursor: (usize, usize),
+}
+
+impl Selection {
+    pub fn lines(&self) -> RangeInclusive<usize> {
+        self.anchor.0.min(self.cursor.0)..=self.anchor.0.max(self.cursor.0)
+    }
+
+    /// Selected columns, for the block selection.
+    pub fn columns(&self) -> Option<RangeInclusive<usize>> {
+        match self.kind {
+            SelectionKind::Line => None,
+            SelectionKind::Block => {
+                Some(self.anchor.1.min(self.cursor.1)..=self.anchor.1.max(self.cursor.1))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Structure {
+    Key,
+    Value,
+    Type,
+}
+
+/// JSON node, with the jq path leading to it.
+pub struct Node {
+    pub path: String,
+    pub key: String,
+    pub kind: String,
+    pub value: String,
+}
+
+impl Node {
+    pub fn is_scalar(&self) -> bool {
+        !matches!(self.kind.as_str(), "object" | "array")
+    }
+}
+
 pub enum PatternMode {
     None,
     Receiving,
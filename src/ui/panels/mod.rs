@@ -9,7 +9,13 @@ pub use command::Command;
 pub mod bookmarks;
 pub use bookmarks::Bookmarks;
 
-pub use super::{super::app, scroller::Scroller};
+pub mod browser;
+pub use browser::Browser;
+
+pub use super::{
+    super::{app, keymap::Action},
+    scroller::Scroller,
+};
 
 #[derive(Debug, PartialEq)]
 pub enum Panel {
@@ -17,14 +23,44 @@ pub enum Panel {
     Output,
     Command,
     Bookmarks,
+    Browser,
+    Popup,
     Help,
 }
 
 pub trait Pane {
     fn scroll_up(&mut self);
     fn scroll_down(&mut self);
-    fn get_pos(&self) -> u16;
+    fn get_pos(&self) -> usize;
     fn get_content(&self) -> String;
     fn get_type(&self) -> &Panel;
     fn handle_event(&mut self, code: KeyCode, modifiers: KeyModifiers) -> app::Signal;
+    fn handle_action(&mut self, _action: Action) -> app::Signal {
+        app::Signal::Nop
+    }
+    /// Whether plain characters are typed in rather than used as shortcuts.
+    fn accepts_text(&self) -> bool {
+        false
+    }
+    /// Whether all the keys go straight to the panel, bypassing the keymap.
+    fn captures_keys(&self) -> bool {
+        false
+    }
+    /// Whether the key goes straight to the panel, bypassing the keymap.
+    fn captures_key(&self, _code: KeyCode) -> bool {
+        self.captures_keys()
+    }
+    /// Mouse click, relative to the panel inner area of the given width.
+    fn click(&mut self, _column: u16, _row: u16, _width: u16) {}
+    fn page_up(&mut self) {}
+    fn page_down(&mut self) {}
+    fn half_page_up(&mut self) {}
+    fn half_page_down(&mut self) {}
+    fn scroll_top(&mut self) {}
+    fn scroll_bottom(&mut self) {}
+    /// Scrolls to the (zero based) line.
+    fn scroll_to(&mut self, _line: usize) {}
+    fn scroll_left(&mut self) {}
+    fn scroll_right(&mut self) {}
+    fn toggle_wrap(&mut self) {}
 }
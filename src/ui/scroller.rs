@@ -20,6 +20,14 @@ impl Scroller {
         }
     }
 
+    pub fn back(&mut self, by: usize) {
+        self.position = self.position.saturating_sub(by);
+    }
+
+    pub fn forward(&mut self, by: usize) {
+        self.position = self.position.saturating_add(by).min(self.max);
+    }
+
     pub fn set_max(&mut self, max: usize) {
         self.max = max;
         if self.position > self.max {
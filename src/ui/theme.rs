@@ -0,0 +1,218 @@
+use super::super::config::ThemeConfig;
+use tui::style::{Color, Modifier, Style};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+pub struct Theme {
+    pub bg: Color,
+    pub fg: Color,
+    pub active: Color,
+    /// No colours at all, as requested by `NO_COLOR`.
+    pub monochrome: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::from_config(&ThemeConfig::default()).expect("Invalid default theme")
+    }
+}
+
+impl Theme {
+    pub fn preset(name: &str) -> Option<Theme> {
+        let (bg, fg, active) = match name {
+            "dark" => (
+                Color::Black,
+                Color::Rgb(184, 184, 184),
+                Color::Rgb(255, 255, 255),
+            ),
+            "light" => (
+                Color::Rgb(250, 250, 250),
+                Color::Rgb(96, 96, 96),
+                Color::Rgb(0, 0, 0),
+            ),
+            "solarized" => (
+                Color::Rgb(0, 43, 54),
+                Color::Rgb(131, 148, 150),
+                Color::Rgb(238, 232, 213),
+            ),
+            "high-contrast" => (Color::Black, Color::White, Color::Yellow),
+            _ => return None,
+        };
+        Some(Theme {
+            bg,
+            fg,
+            active,
+            monochrome: false,
+        })
+    }
+
+    pub fn from_config(config: &ThemeConfig) -> Result<Theme, String> {
+        let mut theme = Theme::preset(&config.preset)
+            .ok_or(format!("Unknown theme preset: {}", config.preset))?;
+        if let Some(bg) = &config.background {
+            theme.bg = parse_color(bg)?;
+        }
+        if let Some(fg) = &config.foreground {
+            theme.fg = parse_color(fg)?;
+        }
+        if let Some(active) = &config.active {
+            theme.active = parse_color(active)?;
+        }
+
+        if std::env::var("NO_COLOR").is_ok_and(|v| !v.is_empty()) {
+            return Ok(Theme::monochrome());
+        }
+        let depth = match config.colors.as_str() {
+            "auto" => detect_depth(),
+            "truecolor" | "24bit" => ColorDepth::TrueColor,
+            "256" => ColorDepth::Ansi256,
+            "16" => ColorDepth::Ansi16,
+            other => return Err(format!("Unknown colour depth: {}", other)),
+        };
+        theme.bg = downsample(theme.bg, depth);
+        theme.fg = downsample(theme.fg, depth);
+        theme.active = downsample(theme.active, depth);
+        Ok(theme)
+    }
+
+    pub fn monochrome() -> Theme {
+        Theme {
+            bg: Color::Reset,
+            fg: Color::Reset,
+            active: Color::Reset,
+            monochrome: true,
+        }
+    }
+
+    /// Style for highlighted text, such as the cursor or the current match.
+    pub fn highlight(&self, style: Style) -> Style {
+        match self.monochrome {
+            true => style.add_modifier(Modifier::REVERSED),
+            false => style.bg(self.fg).fg(self.bg),
+        }
+    }
+
+    /// Style for the panel blocks, active panel standing out.
+    pub fn block(&self, active: bool) -> Style {
+        let style = Style::default().bg(self.bg);
+        match (active, self.monochrome) {
+            (true, true) => style.add_modifier(Modifier::BOLD),
+            (true, false) => style.fg(self.active),
+            (false, _) => style.fg(self.fg),
+        }
+    }
+}
+
+fn detect_depth() -> ColorDepth {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return ColorDepth::TrueColor;
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    if term.contains("256color") {
+        return ColorDepth::Ansi256;
+    }
+    ColorDepth::Ansi16
+}
+
+/// Maps RGB colours to the closest one the terminal can show.
+fn downsample(color: Color, depth: ColorDepth) -> Color {
+    let (r, g, b) = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => return color,
+    };
+    match depth {
+        ColorDepth::TrueColor => color,
+        ColorDepth::Ansi256 => Color::Indexed(to_ansi256(r, g, b)),
+        ColorDepth::Ansi16 => to_ansi16(r, g, b),
+    }
+}
+
+fn to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        return match r {
+            0..=7 => 16,
+            249..=255 => 231,
+            _ => 232 + ((r as u16 - 8) * 24 / 241) as u8,
+        };
+    }
+    let level = |c: u8| match c {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (c - 35) / 40,
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn to_ansi16(r: u8, g: u8, b: u8) -> Color {
+    const PALETTE: [(Color, (i32, i32, i32)); 16] = [
+        (Color::Black, (0, 0, 0)),
+        (Color::Red, (205, 0, 0)),
+        (Color::Green, (0, 205, 0)),
+        (Color::Yellow, (205, 205, 0)),
+        (Color::Blue, (0, 0, 238)),
+        (Color::Magenta, (205, 0, 205)),
+        (Color::Cyan, (0, 205, 205)),
+        (Color::Gray, (229, 229, 229)),
+        (Color::DarkGray, (127, 127, 127)),
+        (Color::LightRed, (255, 0, 0)),
+        (Color::LightGreen, (0, 255, 0)),
+        (Color::LightYellow, (255, 255, 0)),
+        (Color::LightBlue, (92, 92, 255)),
+        (Color::LightMagenta, (255, 0, 255)),
+        (Color::LightCyan, (0, 255, 255)),
+        (Color::White, (255, 255, 255)),
+    ];
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let distance = |(pr, pg, pb): (i32, i32, i32)| {
+        (pr - r) * (pr - r) + (pg - g) * (pg - g) + (pb - b) * (pb - b)
+    };
+    PALETTE
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// Parses colour names, `#rrggbb` hex values and 0-255 palette indexes.
+pub fn parse_color(raw: &str) -> Result<Color, String> {
+    let color = match raw.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.starts_with('#')
+            && hex.len() == 7
+            && hex[1..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            let channel = |from: usize| u8::from_str_radix(&hex[from..from + 2], 16);
+            match (channel(1), channel(3), channel(5)) {
+                (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                _ => return Err(format!("Invalid colour: {}", raw)),
+            }
+        }
+        index => match index.parse::<u8>() {
+            Ok(idx) => Color::Indexed(idx),
+            Err(_) => return Err(format!("Invalid colour: {}", raw)),
+        },
+    };
+    Ok(color)
+}